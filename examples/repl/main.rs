@@ -1,28 +1,55 @@
 use std::io::Write;
+use OpenWebLang::error::error_print;
 use OpenWebLang::lexer::Lexer;
+use OpenWebLang::lint::{check, LintLevels};
+use OpenWebLang::mutability::check_assignments;
 use OpenWebLang::parser::{Expression, ParserInput, Statement};
 
 fn main() {
+    let levels = match LintLevels::from_args(std::env::args().skip(1)) {
+        Ok(levels) => levels,
+        Err(msg) => {
+            eprintln!("{}", msg);
+            std::process::exit(1);
+        }
+    };
     println!("OpenWebLang REPL");
     loop {
         print!(">> ");
         std::io::stdout().flush().unwrap();
         let mut input = String::new();
-        std::io::stdin().read_line(&mut input).unwrap();
+        if std::io::stdin().read_line(&mut input).unwrap() == 0 {
+            return;
+        }
         let lexer = Lexer::new(input);
-        let mut lexer_input = lexer.input.clone();
-        let mut parser_input = ParserInput::from(lexer);
-        while let Some(statement) = Statement::parse(&mut parser_input, &mut lexer_input) {
+        let lexer_input = lexer.input.clone();
+        let mut parser_input = match ParserInput::try_from(lexer) {
+            Ok(parser_input) => parser_input,
+            Err(diagnostic) => {
+                error_print(lexer_input, &diagnostic);
+                continue;
+            }
+        };
+        loop {
+            let statement = match Statement::parse(&mut parser_input) {
+                Ok(Some(statement)) => statement,
+                Ok(None) => break,
+                Err(diagnostic) => {
+                    error_print(lexer_input.clone(), &diagnostic);
+                    break;
+                }
+            };
+            let statements = std::slice::from_ref(&statement);
+            for diagnostic in check(statements, &levels)
+                .into_iter()
+                .chain(check_assignments(statements))
+            {
+                error_print(lexer_input.clone(), &diagnostic);
+            }
             match statement {
-                Statement::Expression(Expression::FunctionCall(name, args)) => {
-                    if name == vec!["exit"] {
-                        return;
-                    } else {
-                        println!(
-                            "{:?}",
-                            Statement::Expression(Expression::FunctionCall(name, args))
-                        );
-                    }
+                Statement::Expression(Expression::Call { callee, .. }) if matches!(&*callee, Expression::Identifier(name) if name == "exit") =>
+                {
+                    return;
                 }
                 _ => {
                     println!("{:?}", statement);
@@ -0,0 +1,32 @@
+// Run with `cargo +nightly fuzz run parse -- -max_len=100000` from the repository root.
+// The default length limit is too short to reach long operator and postfix chains.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use OpenWebLang::error::apply_fixes;
+use OpenWebLang::error::render::{HtmlRenderer, PlainRenderer, Renderer};
+use OpenWebLang::lint::{check, LintLevels};
+use OpenWebLang::mutability::check_assignments;
+use OpenWebLang::parser::{fix, parse};
+use OpenWebLang::resolve::{check_imports, Module};
+
+fuzz_target!(|source: &str| {
+    match parse(source) {
+        Ok(statements) => {
+            check(&statements, &LintLevels::default());
+            check_assignments(&statements);
+            let modules = [Module {
+                path: vec!["main".to_string()],
+                statements,
+            }];
+            check_imports(&modules[0], &modules);
+        }
+        Err(diagnostic) => {
+            PlainRenderer.render(source, &diagnostic);
+            HtmlRenderer.render(source, &diagnostic);
+            apply_fixes(source, &[diagnostic]);
+            fix(source);
+        }
+    }
+});
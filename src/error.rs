@@ -1,53 +1,151 @@
+use crate::error::render::stderr_renderer;
 use crate::lexer::LexerInput;
+use std::fmt::{Display, Formatter};
 use std::ops::Range;
 
-pub fn error_print(input: LexerInput, span: Range<usize>, msg: &str) {
-    let mut line = 1;
-    let mut column = 1;
-    let mut line_begin = 0;
-    for (i, char) in input.clone().enumerate() {
-        if i == span.start {
-            break;
+pub mod render;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Level {
+    Error,
+    Warning,
+}
+impl Display for Level {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Level::Error => write!(f, "error"),
+            Level::Warning => write!(f, "warning"),
         }
-        if char == '\n' {
-            line += 1;
-            column = 1;
-            line_begin = i + 1;
-        } else {
-            column += 1;
+    }
+}
+
+/// How confident we are that a [`Suggestion`] is what the user meant.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Applicability {
+    /// The suggestion is definitely correct and can be applied automatically.
+    MachineApplicable,
+    /// The suggestion is probably correct, but should be reviewed before applying.
+    MaybeIncorrect,
+    /// The suggestion contains placeholders that the user has to fill in.
+    HasPlaceholders,
+}
+
+/// A suggested edit replacing the characters in `span` with `replacement`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Suggestion {
+    pub message: String,
+    pub span: Range<usize>,
+    pub replacement: String,
+    pub applicability: Applicability,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Diagnostic {
+    pub level: Level,
+    pub message: String,
+    pub span: Range<usize>,
+    pub suggestions: Vec<Suggestion>,
+    /// The name of the lint that produced this diagnostic, if any.
+    pub lint: Option<&'static str>,
+}
+impl Diagnostic {
+    pub fn error(span: Range<usize>, message: impl Into<String>) -> Self {
+        Diagnostic {
+            level: Level::Error,
+            message: message.into(),
+            span,
+            suggestions: Vec::new(),
+            lint: None,
         }
     }
-    let mut line_len = 0;
-    for char in input.clone().skip(line_begin) {
-        if char == '\n' {
-            break;
+    pub fn with_suggestion(
+        mut self,
+        message: impl Into<String>,
+        span: Range<usize>,
+        replacement: impl Into<String>,
+        applicability: Applicability,
+    ) -> Self {
+        self.suggestions.push(Suggestion {
+            message: message.into(),
+            span,
+            replacement: replacement.into(),
+            applicability,
+        });
+        self
+    }
+}
+
+/// Applies every machine-applicable suggestion of `diagnostics` to `source`.
+///
+/// Spans are character offsets, as produced by the lexer. When two suggestions
+/// overlap, only the one starting first is applied.
+pub fn apply_fixes(source: &str, diagnostics: &[Diagnostic]) -> String {
+    let mut suggestions = diagnostics
+        .iter()
+        .flat_map(|d| d.suggestions.iter())
+        .filter(|s| s.applicability == Applicability::MachineApplicable)
+        .collect::<Vec<_>>();
+    suggestions.sort_by_key(|s| (s.span.start, s.span.end));
+    let chars = source.chars().collect::<Vec<_>>();
+    let mut fixed = String::with_capacity(source.len());
+    let mut pos = 0;
+    for suggestion in suggestions {
+        if suggestion.span.start < pos || suggestion.span.end > chars.len() {
+            continue;
         }
-        line_len += 1;
+        fixed.extend(&chars[pos..suggestion.span.start]);
+        fixed.push_str(&suggestion.replacement);
+        pos = suggestion.span.end;
     }
-    let mut print_area = String::new();
-    for char in input.skip(line_begin) {
-        if char == '\n' {
-            break;
+    fixed.extend(&chars[pos..]);
+    fixed
+}
+
+/// Edit distance between two strings, counted in characters, where swapping two
+/// adjacent characters counts as a single edit.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a = a.chars().collect::<Vec<_>>();
+    let b = b.chars().collect::<Vec<_>>();
+    let mut rows = vec![(0..=b.len()).collect::<Vec<_>>()];
+    for i in 1..=a.len() {
+        let mut row = vec![i];
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            let mut distance = (rows[i - 1][j - 1] + cost)
+                .min(rows[i - 1][j] + 1)
+                .min(row[j - 1] + 1);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(rows[i - 2][j - 2] + 1);
+            }
+            row.push(distance);
         }
-        print_area.push(char);
+        rows.push(row);
     }
-    eprintln!("{}", print_area);
-    eprintln!(
-        "{}{}",
-        " ".repeat(column - 1),
-        "^".repeat((span.end - span.start).min(line_len - (column - 1)))
-    );
-    #[cfg(debug_assertions)]
-    eprintln!("{}:{} ({:?}): {}", line, column, span, msg);
-    #[cfg(not(debug_assertions))]
-    eprintln!("{}:{}: {}", line, column, msg);
+    rows[a.len()][b.len()]
+}
+
+/// Finds the candidate closest to `name`, if any is close enough to be a likely typo.
+pub fn closest_match<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Option<&'a str> {
+    let max_distance = (name.chars().count() / 3).max(1);
+    candidates
+        .into_iter()
+        .filter(|&candidate| candidate != name)
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|&(distance, _)| distance <= max_distance)
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, candidate)| candidate)
+}
+
+pub fn error_print(input: LexerInput, diagnostic: &Diagnostic) {
+    let source = input.clone().collect::<String>();
+    eprint!("{}", stderr_renderer().render(&source, diagnostic));
 }
 
 macro_rules! error {
-    ($input:expr, $span:expr, $($tt:tt)*) => {
-        {
-            $crate::error::error_print($input, $span, format!($($tt)*).as_str());
-            std::process::exit(-1);
-        }
+    ($span:expr, $($tt:tt)*) => {
+        return Err($crate::error::Diagnostic::error($span, format!($($tt)*)))
     };
 }
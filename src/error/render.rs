@@ -0,0 +1,154 @@
+use crate::error::{Diagnostic, Level};
+use std::io::IsTerminal;
+
+/// The role a piece of text plays in a rendered diagnostic.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Style {
+    /// The whole rendered diagnostic.
+    Diagnostic(Level),
+    /// The `line:column` location and the line number gutter.
+    Location,
+    /// The severity label, e.g. `error`.
+    Level(Level),
+    Message,
+    /// The quoted line of source code.
+    Source,
+    /// The `^^^` underline below the source.
+    Caret(Level),
+    /// The `help` label in front of suggestions.
+    Help,
+}
+
+/// Turns a [`Diagnostic`] into text. Implementors only decide how each [`Style`]
+/// looks, the layout is shared by all of them.
+pub trait Renderer {
+    fn paint(&self, style: Style, text: &str) -> String;
+
+    fn render(&self, source: &str, diagnostic: &Diagnostic) -> String {
+        let chars = source.chars().collect::<Vec<_>>();
+        let start = diagnostic.span.start.min(chars.len());
+        let line_begin = chars[..start]
+            .iter()
+            .rposition(|&c| c == '\n')
+            .map_or(0, |i| i + 1);
+        let line_end = chars[start..]
+            .iter()
+            .position(|&c| c == '\n')
+            .map_or(chars.len(), |i| start + i);
+        let line = chars[..start].iter().filter(|&&c| c == '\n').count() + 1;
+        let column = start - line_begin + 1;
+        let carets = diagnostic
+            .span
+            .end
+            .saturating_sub(start)
+            .min(line_end - start)
+            .max(1);
+        let source_line = chars[line_begin..line_end].iter().collect::<String>();
+        let gutter = line.to_string();
+
+        let mut out = String::new();
+        out += &format!(
+            "{} {}\n",
+            self.paint(Style::Location, &format!("{} |", gutter)),
+            self.paint(Style::Source, &source_line)
+        );
+        out += &format!(
+            "{} {}{}\n",
+            self.paint(Style::Location, &format!("{} |", " ".repeat(gutter.len()))),
+            " ".repeat(column - 1),
+            self.paint(Style::Caret(diagnostic.level), &"^".repeat(carets))
+        );
+        #[cfg(debug_assertions)]
+        let location = format!("{}:{} ({:?}):", line, column, diagnostic.span);
+        #[cfg(not(debug_assertions))]
+        let location = format!("{}:{}:", line, column);
+        out += &format!(
+            "{} {} {}\n",
+            self.paint(Style::Location, &location),
+            self.paint(
+                Style::Level(diagnostic.level),
+                &match diagnostic.lint {
+                    Some(lint) => format!("{}[{}]:", diagnostic.level, lint),
+                    None => format!("{}:", diagnostic.level),
+                }
+            ),
+            self.paint(Style::Message, &diagnostic.message)
+        );
+        for suggestion in &diagnostic.suggestions {
+            out += &format!(
+                "{} {}\n",
+                self.paint(Style::Help, "help:"),
+                self.paint(Style::Message, &suggestion.message)
+            );
+        }
+        self.paint(Style::Diagnostic(diagnostic.level), &out)
+    }
+}
+
+/// Renders diagnostics without any styling.
+pub struct PlainRenderer;
+impl Renderer for PlainRenderer {
+    fn paint(&self, _: Style, text: &str) -> String {
+        text.to_string()
+    }
+}
+
+/// Renders diagnostics with ANSI escape codes for terminals.
+pub struct AnsiRenderer;
+impl Renderer for AnsiRenderer {
+    fn paint(&self, style: Style, text: &str) -> String {
+        let code = match style {
+            Style::Diagnostic(_) | Style::Source => return text.to_string(),
+            Style::Location => "1;34",
+            Style::Level(Level::Error) | Style::Caret(Level::Error) => "1;31",
+            Style::Level(Level::Warning) | Style::Caret(Level::Warning) => "1;33",
+            Style::Message => "1",
+            Style::Help => "1;36",
+        };
+        format!("\x1b[{}m{}\x1b[0m", code, text)
+    }
+}
+
+/// Renders diagnostics as HTML, for the browser playground.
+///
+/// Every piece is wrapped in a `<span>` with an `owl-*` class so it can be styled with CSS.
+pub struct HtmlRenderer;
+impl Renderer for HtmlRenderer {
+    fn paint(&self, style: Style, text: &str) -> String {
+        let class = match style {
+            Style::Diagnostic(level) => {
+                return format!("<pre class=\"owl-diagnostic owl-{}\">{}</pre>", level, text)
+            }
+            Style::Location => "owl-location",
+            Style::Level(_) => "owl-level",
+            Style::Message => "owl-message",
+            Style::Source => "owl-source",
+            Style::Caret(_) => "owl-caret",
+            Style::Help => "owl-help",
+        };
+        let mut escaped = String::with_capacity(text.len());
+        for c in text.chars() {
+            match c {
+                '<' => escaped.push_str("&lt;"),
+                '>' => escaped.push_str("&gt;"),
+                '&' => escaped.push_str("&amp;"),
+                '"' => escaped.push_str("&quot;"),
+                '\'' => escaped.push_str("&#39;"),
+                c => escaped.push(c),
+            }
+        }
+        format!("<span class=\"{}\">{}</span>", class, escaped)
+    }
+}
+
+/// Picks the renderer for stderr: colored when it is a terminal, plain when it is
+/// redirected, `NO_COLOR` is set or `TERM` is `dumb`.
+pub fn stderr_renderer() -> Box<dyn Renderer> {
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+    let dumb = std::env::var_os("TERM").is_some_and(|v| v == "dumb");
+    if no_color || dumb || !std::io::stderr().is_terminal() {
+        Box::new(PlainRenderer)
+    } else {
+        Box::new(AnsiRenderer)
+    }
+}
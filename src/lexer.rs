@@ -1,3 +1,4 @@
+use crate::error::Diagnostic;
 use crate::lexer::token::keyword::Keyword;
 use crate::lexer::token::Token;
 use std::ops::Range;
@@ -11,11 +12,14 @@ pub struct LexerInput {
 }
 impl LexerInput {
     fn next(&mut self) -> Option<char> {
-        self.pos += 1;
-        self.data.get(self.pos - 1).cloned()
+        let c = self.data.get(self.pos).cloned();
+        if c.is_some() {
+            self.pos += 1;
+        }
+        c
     }
     fn peek_for(&mut self, c: char) -> bool {
-        self.data.get(self.pos).map_or(false, |&ch| {
+        self.data.get(self.pos).is_some_and(|&ch| {
             if ch == c {
                 self.pos += 1;
                 true
@@ -25,26 +29,24 @@ impl LexerInput {
         })
     }
     fn peek_for_str(&mut self, s: &str) -> bool {
-        let mut matches = true;
+        let mut len = 0;
         for (i, char) in s.chars().enumerate() {
-            if self.data[self.pos + i] != char {
-                matches = false;
-                break;
+            if self.data.get(self.pos + i) != Some(&char) {
+                return false;
             }
+            len += 1;
         }
-        if matches {
-            self.pos += s.len();
-        }
-        matches
+        self.pos += len;
+        true
     }
     fn peek<F>(&mut self, func: F) -> bool
     where
         F: Fn(&char) -> bool,
     {
-        self.data.get(self.pos).map_or(false, func)
+        self.data.get(self.pos).is_some_and(func)
     }
     pub fn rewind(&mut self) {
-        self.pos -= 1;
+        self.pos = self.pos.saturating_sub(1);
     }
     pub fn pos(&self) -> usize {
         self.pos
@@ -78,15 +80,33 @@ impl Clone for LexerInput {
     }
 }
 
-fn parse_number(input: &mut LexerInput) -> Token {
+fn parse_number(input: &mut LexerInput, start: usize) -> Result<Token, Diagnostic> {
     let mut number = String::new();
     while input.peek(char::is_ascii_digit) {
         number.push(input.next().unwrap());
     }
+    // Only a dot followed by a digit makes a float, so `a.0.b` and `0..10` stay intact
+    if input.peek(|&c| c == '.')
+        && input
+            .data
+            .get(input.pos + 1)
+            .is_some_and(char::is_ascii_digit)
+    {
+        number.push(input.next().unwrap());
+        while input.peek(char::is_ascii_digit) {
+            number.push(input.next().unwrap());
+        }
+    }
     if number.contains('.') {
-        Token::FloatLiteral(f64::from_str(&number).unwrap())
+        match f64::from_str(&number) {
+            Ok(n) => Ok(Token::FloatLiteral(n)),
+            Err(_) => error!(start..input.pos, "Invalid float literal"),
+        }
     } else {
-        Token::UnsignedLiteral(u64::from_str(&number).unwrap())
+        match u64::from_str(&number) {
+            Ok(n) => Ok(Token::UnsignedLiteral(n)),
+            Err(_) => error!(start..input.pos, "Integer literal is too large"),
+        }
     }
 }
 
@@ -100,14 +120,36 @@ impl Lexer {
             input: input.into(),
         }
     }
-}
-impl Iterator for Lexer {
-    type Item = (Token, Range<usize>);
-
-    fn next(&mut self) -> Option<Self::Item> {
+    /// Skips whitespace and comments.
+    fn skip_trivia(&mut self) {
+        loop {
+            if self.input.peek(|c| c.is_whitespace()) {
+                self.input.next();
+            } else if self.input.peek_for_str("//") {
+                while let Some(ch) = self.input.next() {
+                    if ch == '\n' {
+                        break;
+                    }
+                }
+            } else if self.input.peek_for_str("/*") {
+                while let Some(ch) = self.input.next() {
+                    if ch == '*' && self.input.peek_for('/') {
+                        break;
+                    }
+                }
+            } else {
+                break;
+            }
+        }
+    }
+    fn next_token(&mut self) -> Result<Option<(Token, Range<usize>)>, Diagnostic> {
+        self.skip_trivia();
         let start = self.input.pos;
-        Some((
-            match self.input.next()? {
+        let Some(c) = self.input.next() else {
+            return Ok(None);
+        };
+        Ok(Some((
+            match c {
                 '(' => Token::OpenParen,
                 ')' => Token::CloseParen,
                 '{' => Token::OpenBrace,
@@ -120,16 +162,9 @@ impl Iterator for Lexer {
                 },
                 '-' => match self.input.peek_for('=') {
                     true => Token::SubAssign,
-                    false => match self.input.peek(char::is_ascii_digit) {
-                        true => match parse_number(&mut self.input) {
-                            Token::UnsignedLiteral(n) => Token::SignedLiteral(-(n as i64)),
-                            Token::FloatLiteral(n) => Token::FloatLiteral(n),
-                            _ => unreachable!(),
-                        },
-                        false => match self.input.peek_for('>') {
-                            true => Token::Arrow,
-                            false => Token::Sub,
-                        },
+                    false => match self.input.peek_for('>') {
+                        true => Token::Arrow,
+                        false => Token::Sub,
                     },
                 },
                 '*' => match self.input.peek_for('=') {
@@ -144,27 +179,7 @@ impl Iterator for Lexer {
                 },
                 '/' => match self.input.peek_for('=') {
                     true => Token::DivAssign,
-                    false => match self.input.peek_for('/') {
-                        true => {
-                            while let Some(ch) = self.input.next() {
-                                if ch == '\n' {
-                                    break;
-                                }
-                            }
-                            return self.next();
-                        }
-                        false => match self.input.peek_for('*') {
-                            true => {
-                                while let Some(ch) = self.input.next() {
-                                    if ch == '*' && self.input.peek_for('/') {
-                                        break;
-                                    }
-                                }
-                                return self.next();
-                            }
-                            false => Token::Div,
-                        },
-                    },
+                    false => Token::Div,
                 },
                 '%' => match self.input.peek_for('=') {
                     true => Token::ModAssign,
@@ -194,20 +209,52 @@ impl Iterator for Lexer {
                 },
                 '=' => match self.input.peek_for('=') {
                     true => Token::Eq,
-                    false => Token::Assign,
+                    false => match self.input.peek_for('>') {
+                        true => Token::FatArrow,
+                        false => Token::Assign,
+                    },
                 },
-                '<' => match self.input.peek_for('=') {
-                    true => Token::Le,
-                    false => Token::Lt,
+                '<' => match self.input.peek_for('<') {
+                    true => match self.input.peek_for('=') {
+                        true => Token::ShlAssign,
+                        false => Token::Shl,
+                    },
+                    false => match self.input.peek_for('=') {
+                        true => Token::Le,
+                        false => Token::Lt,
+                    },
                 },
-                '>' => match self.input.peek_for('=') {
-                    true => Token::Ge,
-                    false => Token::Gt,
+                '>' => match self.input.peek_for('>') {
+                    true => match self.input.peek_for('=') {
+                        true => Token::ShrAssign,
+                        false => Token::Shr,
+                    },
+                    false => match self.input.peek_for('=') {
+                        true => Token::Ge,
+                        false => Token::Gt,
+                    },
                 },
                 ',' => Token::Comma,
                 ';' => Token::Semicolon,
-                '.' => Token::Dot,
+                '.' => match self.input.peek_for('.') {
+                    true => match self.input.peek_for('=') {
+                        true => Token::DotDotEq,
+                        false => match self.input.peek_for('.') {
+                            true => Token::Ellipsis,
+                            false => Token::DotDot,
+                        },
+                    },
+                    false => Token::Dot,
+                },
                 ':' => Token::Colon,
+                '@' => Token::At,
+                '?' => match self.input.peek_for('?') {
+                    true => Token::Coalesce,
+                    false => match self.input.peek_for('.') {
+                        true => Token::QuestionDot,
+                        false => Token::Question,
+                    },
+                },
                 '"' => {
                     let mut string = String::new();
                     let mut ended = false;
@@ -217,67 +264,74 @@ impl Iterator for Lexer {
                             break;
                         }
                         if ch == '\\' {
-                            match self.input.next()? {
-                                'n' => string.push('\n'),
-                                'r' => string.push('\r'),
-                                't' => string.push('\t'),
-                                '\\' => string.push('\\'),
-                                '"' => string.push('"'),
-                                'u' => {
+                            match self.input.next() {
+                                Some('n') => string.push('\n'),
+                                Some('r') => string.push('\r'),
+                                Some('t') => string.push('\t'),
+                                Some('\\') => string.push('\\'),
+                                Some('"') => string.push('"'),
+                                Some('u') => {
                                     let mut hex = String::new();
                                     for _ in 0..4 {
-                                        hex.push(self.input.next()?);
+                                        match self.input.next() {
+                                            Some(c) => hex.push(c),
+                                            None => {
+                                                error!(start..self.input.pos, "Unterminated string")
+                                            }
+                                        }
+                                    }
+                                    match u32::from_str_radix(&hex, 16)
+                                        .ok()
+                                        .and_then(char::from_u32)
+                                    {
+                                        Some(c) => string.push(c),
+                                        None => {
+                                            error!(start..self.input.pos, "Invalid unicode escape")
+                                        }
                                     }
-                                    string.push(
-                                        char::from_u32(u32::from_str_radix(&hex, 16).unwrap())
-                                            .unwrap(),
-                                    );
                                 }
-                                _ => error!(
-                                    self.input.clone(),
-                                    start..self.input.pos,
-                                    "Invalid escape sequence"
-                                ),
+                                Some(_) => error!(start..self.input.pos, "Invalid escape sequence"),
+                                None => error!(start..self.input.pos, "Unterminated string"),
                             }
+                        } else {
+                            string.push(ch);
                         }
-                        string.push(ch);
                     }
                     if !ended {
-                        error!(
-                            self.input.clone(),
-                            start..self.input.pos,
-                            "Unterminated string"
-                        );
+                        error!(start..self.input.pos, "Unterminated string");
                     }
                     Token::StringLiteral(string)
                 }
+                // A quote followed by an identifier is a label like `'outer`, unless it's
+                // a single character closed by another quote
+                '\'' if self.input.peek(|&c| c.is_alphabetic() || c == '_')
+                    && self.input.data.get(self.input.pos + 1) != Some(&'\'') =>
+                {
+                    let mut label = String::new();
+                    while self.input.peek(|&c| c.is_alphanumeric() || c == '_') {
+                        label.push(self.input.next().unwrap());
+                    }
+                    Token::Label(label)
+                }
                 '\'' => {
                     if let Some(c) = self.input.next() {
                         if self.input.peek_for('\'') {
                             Token::CharLiteral(c)
                         } else {
-                            error!(
-                                self.input.clone(),
-                                start..self.input.pos,
-                                "Invalid character literal"
-                            )
+                            error!(start..self.input.pos, "Invalid character literal")
                         }
                     } else {
-                        error!(
-                            self.input.clone(),
-                            start..self.input.pos,
-                            "Unexpected end of input"
-                        )
+                        error!(start..self.input.pos, "Unexpected end of input")
                     }
                 }
                 c if c.is_ascii_digit() => {
                     self.input.rewind();
-                    parse_number(&mut self.input)
+                    parse_number(&mut self.input, start)?
                 }
-                c if c.is_alphabetic() => {
+                c if c.is_alphabetic() || c == '_' => {
                     let mut string = String::new();
                     string.push(c);
-                    while self.input.peek(|&c| c.is_alphanumeric()) {
+                    while self.input.peek(|&c| c.is_alphanumeric() || c == '_') {
                         string.push(self.input.next().unwrap());
                     }
                     if let Ok(keyword) = Keyword::from_str(&string) {
@@ -286,14 +340,16 @@ impl Iterator for Lexer {
                         Token::Identifier(string)
                     }
                 }
-                c if c.is_whitespace() => return self.next(),
-                _ => error!(
-                    self.input.clone(),
-                    start..self.input.pos,
-                    "Unexpected character"
-                ),
+                _ => error!(start..self.input.pos, "Unexpected character"),
             },
             start..self.input.pos,
-        ))
+        )))
+    }
+}
+impl Iterator for Lexer {
+    type Item = Result<(Token, Range<usize>), Diagnostic>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_token().transpose()
     }
 }
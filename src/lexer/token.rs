@@ -6,11 +6,12 @@ pub mod keyword;
 pub enum Token {
     Identifier(String),
     Keyword(Keyword),
-    SignedLiteral(i64),
     UnsignedLiteral(u64),
     FloatLiteral(f64),
     StringLiteral(String),
     CharLiteral(char),
+    /// 'label
+    Label(String),
 
     // Brackets
     /// (
@@ -53,6 +54,8 @@ pub enum Token {
     And,
     /// a || b
     Or,
+    /// a ?? b
+    Coalesce,
     /// !a
     Not,
     /// a == b
@@ -93,6 +96,14 @@ pub enum Token {
     ShrAssign,
     /// ->
     Arrow,
+    /// =>
+    FatArrow,
+    /// a..b
+    DotDot,
+    /// a..=b
+    DotDotEq,
+    /// ...a
+    Ellipsis,
 
     // Delimiters
     /// ,
@@ -103,6 +114,12 @@ pub enum Token {
     Colon,
     /// .
     Dot,
+    /// a?.b
+    QuestionDot,
+    /// @
+    At,
+    /// T? or a ? b : c
+    Question,
 }
 impl Token {
     pub fn is_binary_op(&self) -> bool {
@@ -121,6 +138,7 @@ impl Token {
                 | Token::Shr
                 | Token::And
                 | Token::Or
+                | Token::Coalesce
                 | Token::Not
                 | Token::Eq
                 | Token::Ne
@@ -130,9 +148,64 @@ impl Token {
                 | Token::Ge
         )
     }
+    /// How tightly a binary operator binds, where higher binds tighter, or `None` if
+    /// the token isn't one. `**` is right-associative and all others are left-associative.
+    pub fn precedence(&self) -> Option<u8> {
+        Some(match self {
+            Token::Coalesce => 1,
+            Token::Or => 2,
+            Token::And => 3,
+            Token::BitOr => 4,
+            Token::BitXor => 5,
+            Token::BitAnd => 6,
+            Token::Eq | Token::Ne => 7,
+            Token::Lt | Token::Le | Token::Gt | Token::Ge => 8,
+            Token::Shl | Token::Shr => 9,
+            Token::Add | Token::Sub => 10,
+            Token::Mul | Token::Div | Token::Mod => 11,
+            Token::Pow => 12,
+            _ => return None,
+        })
+    }
     pub fn is_unary_op(&self) -> bool {
         matches!(self, Token::Not | Token::Sub)
     }
+    pub fn is_literal(&self) -> bool {
+        matches!(
+            self,
+            Token::StringLiteral(_)
+                | Token::UnsignedLiteral(_)
+                | Token::FloatLiteral(_)
+                | Token::CharLiteral(_)
+                | Token::Keyword(Keyword::True | Keyword::False | Keyword::Null)
+        )
+    }
+    pub fn begins_expression(&self) -> bool {
+        matches!(
+            self,
+            Token::Identifier(_)
+                | Token::OpenParen
+                | Token::OpenBrace
+                | Token::OpenBracket
+                | Token::Label(_)
+                | Token::Keyword(
+                    Keyword::If
+                        | Keyword::Function
+                        | Keyword::Super
+                        | Keyword::SelfValue
+                        | Keyword::New
+                        | Keyword::Match
+                        | Keyword::While
+                        | Keyword::Loop
+                        | Keyword::For
+                        | Keyword::Break
+                        | Keyword::Continue
+                        | Keyword::Try
+                        | Keyword::Throw
+                )
+        ) || self.is_literal()
+            || self.is_unary_op()
+    }
     pub fn is_assign_op(&self) -> bool {
         matches!(
             self,
@@ -10,34 +10,122 @@ pub enum Keyword {
     Function,
     For,
     If,
+    In,
     Loop,
     Match,
+    Null,
     Return,
     True,
     While,
     Class,
     Import,
+    Abstract,
+    Extends,
+    Implements,
+    Interface,
+    Super,
+    Constructor,
+    New,
+    SelfValue,
+    Static,
+    Pub,
+    Private,
+    Enum,
+    As,
+    Const,
+    Mut,
+    Try,
+    Catch,
+    Finally,
+    Throw,
+}
+impl Keyword {
+    pub const ALL: &'static [Keyword] = &[
+        Keyword::Break,
+        Keyword::Continue,
+        Keyword::Else,
+        Keyword::False,
+        Keyword::Let,
+        Keyword::Function,
+        Keyword::For,
+        Keyword::If,
+        Keyword::In,
+        Keyword::Loop,
+        Keyword::Match,
+        Keyword::Null,
+        Keyword::Return,
+        Keyword::True,
+        Keyword::While,
+        Keyword::Class,
+        Keyword::Import,
+        Keyword::Abstract,
+        Keyword::Extends,
+        Keyword::Implements,
+        Keyword::Interface,
+        Keyword::Super,
+        Keyword::Constructor,
+        Keyword::New,
+        Keyword::SelfValue,
+        Keyword::Static,
+        Keyword::Pub,
+        Keyword::Private,
+        Keyword::Enum,
+        Keyword::As,
+        Keyword::Const,
+        Keyword::Mut,
+        Keyword::Try,
+        Keyword::Catch,
+        Keyword::Finally,
+        Keyword::Throw,
+    ];
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Keyword::Break => "break",
+            Keyword::Continue => "continue",
+            Keyword::Else => "else",
+            Keyword::False => "false",
+            Keyword::Let => "let",
+            Keyword::Function => "function",
+            Keyword::For => "for",
+            Keyword::If => "if",
+            Keyword::In => "in",
+            Keyword::Loop => "loop",
+            Keyword::Match => "match",
+            Keyword::Null => "null",
+            Keyword::Return => "return",
+            Keyword::True => "true",
+            Keyword::While => "while",
+            Keyword::Class => "class",
+            Keyword::Import => "import",
+            Keyword::Abstract => "abstract",
+            Keyword::Extends => "extends",
+            Keyword::Implements => "implements",
+            Keyword::Interface => "interface",
+            Keyword::Super => "super",
+            Keyword::Constructor => "constructor",
+            Keyword::New => "new",
+            Keyword::SelfValue => "self",
+            Keyword::Static => "static",
+            Keyword::Pub => "pub",
+            Keyword::Private => "private",
+            Keyword::Enum => "enum",
+            Keyword::As => "as",
+            Keyword::Const => "const",
+            Keyword::Mut => "mut",
+            Keyword::Try => "try",
+            Keyword::Catch => "catch",
+            Keyword::Finally => "finally",
+            Keyword::Throw => "throw",
+        }
+    }
 }
 impl FromStr for Keyword {
     type Err = ();
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "break" => Ok(Keyword::Break),
-            "continue" => Ok(Keyword::Continue),
-            "else" => Ok(Keyword::Else),
-            "false" => Ok(Keyword::False),
-            "let" => Ok(Keyword::Let),
-            "function" => Ok(Keyword::Function),
-            "for" => Ok(Keyword::For),
-            "if" => Ok(Keyword::If),
-            "loop" => Ok(Keyword::Loop),
-            "match" => Ok(Keyword::Match),
-            "return" => Ok(Keyword::Return),
-            "true" => Ok(Keyword::True),
-            "while" => Ok(Keyword::While),
-            "class" => Ok(Keyword::Class),
-            "import" => Ok(Keyword::Import),
-            _ => Err(()),
-        }
+        Keyword::ALL
+            .iter()
+            .find(|k| k.as_str() == s)
+            .cloned()
+            .ok_or(())
     }
 }
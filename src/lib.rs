@@ -1,19 +1,32 @@
+#![allow(non_snake_case)]
+
 #[macro_use]
 pub mod error;
 pub mod lexer;
+pub mod lint;
+pub mod mutability;
 pub mod parser;
+pub mod resolve;
 
 #[cfg(test)]
 mod test {
-    use crate::lexer::{Lexer, LexerInput};
-    use crate::parser::{Expression, ParserInput, Statement};
+    use crate::error::render::{AnsiRenderer, HtmlRenderer, PlainRenderer, Renderer};
+    use crate::error::{apply_fixes, closest_match, Applicability, Level};
+    use crate::lexer::token::Token;
+    use crate::lexer::Lexer;
+    use crate::lint::{check, LintLevels};
+    use crate::mutability::check_assignments;
+    use crate::parser::{
+        fix, parse, unparsed, Expression, Import, Mutability, ParserInput, Pattern, Statement, Type,
+    };
+    use crate::resolve::{check_imports, Module};
     use std::time::Instant;
 
     #[test]
     fn lexer() {
         let lexer = Lexer::new(include_str!("example.owl"));
         let start = Instant::now();
-        let tokens = lexer.collect::<Vec<_>>();
+        let tokens = lexer.collect::<Result<Vec<_>, _>>().unwrap();
         println!("Done in {:?}", start.elapsed());
         println!("{:?}", tokens);
     }
@@ -21,10 +34,1606 @@ mod test {
     #[test]
     fn parser() {
         let lexer = Lexer::new(include_str!("example.owl"));
-        let mut lexer_input = lexer.input.clone();
-        let mut parser_input = ParserInput::from(lexer);
-        while let Some(statement) = Statement::parse(&mut parser_input, &mut lexer_input) {
+        let mut parser_input = ParserInput::try_from(lexer).unwrap();
+        while let Some(statement) = Statement::parse(&mut parser_input).unwrap() {
             println!("{:?}", statement);
         }
     }
+
+    #[test]
+    fn fixes() {
+        let source = "let x = foo(1 2)\nlet y = 3";
+        let diagnostic = parse(source).unwrap_err();
+        assert_eq!(diagnostic.message, "Expected comma");
+        assert_eq!(
+            apply_fixes(source, &[diagnostic]),
+            "let x = foo(1, 2)\nlet y = 3"
+        );
+        assert_eq!(fix(source), "let x = foo(1, 2);\nlet y = 3;");
+        assert!(parse(&fix(source)).is_ok());
+
+        // Missing delimiters are only guesses, as the input may have an extra token instead
+        let source = "function f( { }";
+        let diagnostic = parse(source).unwrap_err();
+        assert_eq!(diagnostic.message, "Expected colon");
+        assert_eq!(
+            diagnostic.suggestions[0].applicability,
+            Applicability::MaybeIncorrect
+        );
+        assert_eq!(fix(source), source);
+        assert_eq!(
+            fix("let x = foo(1, 2\nlet y = 3"),
+            "let x = foo(1, 2\nlet y = 3"
+        );
+    }
+
+    #[test]
+    fn did_you_mean() {
+        let diagnostic = parse("fucntion main() {}").unwrap_err();
+        assert_eq!(diagnostic.message, "Unexpected identifier `fucntion`");
+        assert_eq!(
+            diagnostic.suggestions[0].message,
+            "did you mean `function`?"
+        );
+        assert_eq!(diagnostic.suggestions[0].replacement, "function");
+        assert_eq!(
+            diagnostic.suggestions[0].applicability,
+            Applicability::MaybeIncorrect
+        );
+        let diagnostic = parse("improt Foo.Bar;").unwrap_err();
+        assert_eq!(diagnostic.suggestions[0].replacement, "import");
+        let diagnostic = parse("a b;").unwrap_err();
+        assert_eq!(diagnostic.message, "Expected semicolon");
+        assert_eq!(closest_match("lte", ["let", "loop"]), Some("let"));
+        assert_eq!(closest_match("window", ["let", "loop"]), None);
+    }
+
+    #[test]
+    fn renderers() {
+        let source = "let a = 1;\nlet b = <2;";
+        let diagnostic = parse(source).unwrap_err();
+        let plain = PlainRenderer.render(source, &diagnostic);
+        assert!(plain.starts_with("2 | let b = <2;\n  |     ^\n2:5"));
+        assert!(plain.contains("error: Expected expression"));
+        let ansi = AnsiRenderer.render(source, &diagnostic);
+        assert!(ansi.contains("\x1b[1;31m^\x1b[0m"));
+        let html = HtmlRenderer.render(source, &diagnostic);
+        assert!(html.starts_with("<pre class=\"owl-diagnostic owl-error\">"));
+        assert!(html.contains("let b = &lt;2;"));
+    }
+
+    #[test]
+    fn lints() {
+        let source = "function main() {
+            let x = 1;
+            let _y = 2;
+            @allow(unused_variable) let z = 3;
+            let w = 4;
+            Console.log(w);
+            let mut v = 0;
+            v = 1;
+            let s = 1;
+            function h(s: u8) -> u8 { s }
+        }
+        @deny(unused_variable)
+        function f() { let q = 1; }
+        @allow(unused_varaible)
+        function g() {}";
+        let statements = parse(source).unwrap();
+        let diagnostics = check(&statements, &LintLevels::default());
+        let summary = diagnostics
+            .iter()
+            .map(|d| (d.level, d.lint.unwrap(), d.message.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            [
+                (Level::Warning, "unused_variable", "Unused variable `x`"),
+                (Level::Warning, "unused_variable", "Unused variable `v`"),
+                (Level::Warning, "unused_variable", "Unused variable `s`"),
+                (Level::Error, "unused_variable", "Unused variable `q`"),
+                (
+                    Level::Warning,
+                    "unknown_lints",
+                    "Unknown lint `unused_varaible`"
+                ),
+            ]
+        );
+        assert_eq!(
+            diagnostics[4].suggestions[0].replacement,
+            "@allow(unused_variable)"
+        );
+        let levels = LintLevels::from_args(["-A", "unused_variable", "-Dunknown_lints"]).unwrap();
+        let diagnostics = check(&statements, &levels);
+        // The in-source `@deny` still wins over the command line
+        assert_eq!(diagnostics.len(), 2);
+        assert!(diagnostics.iter().all(|d| d.level == Level::Error));
+        assert!(LintLevels::from_args(["-W", "nope"]).is_err());
+    }
+
+    #[test]
+    fn attributes() {
+        let source = "@export @wasm_import(\"env\", \"log\") function log(s: string);
+        @deprecated(\"use Circle\") class Round {
+            @inline function area() -> f64 { 0.0 }
+            @deprecated let radius: f64;
+        }
+        enum Shape { @deprecated(\"use Rect\") Square, @test Rect }
+        function main() {
+            @test let x = 1;
+            @inline Console.log(x);
+            @deprecated(1) function f() {}
+            @wasm_import(\"env\") function g();
+            @export(x) let y = 2;
+            @allow(invalid_attributes) @test let z = 3;
+            @custom(anything) f(y, z);
+        }";
+        let statements = parse(source).unwrap();
+        assert_eq!(
+            statements[0]
+                .attribute("wasm_import")
+                .and_then(|a| a.string_args()),
+            Some(vec!["env", "log"])
+        );
+        assert!(statements[0].attribute("test").is_none());
+        let Statement::EnumDecl { variants, .. } = &statements[2] else {
+            panic!("{:?}", statements[2]);
+        };
+        assert_eq!(variants[0].attributes[0].name, "deprecated");
+        let messages = check(&statements, &LintLevels::default())
+            .into_iter()
+            .map(|d| d.message)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            [
+                "`@test` can only be used on functions",
+                "`@test` can only be used on functions",
+                "`@inline` can only be used on functions",
+                "Expected `@deprecated(\"reason\")`",
+                "Expected `@wasm_import(\"module\", \"name\")`",
+                "`@export` takes no arguments",
+            ]
+        );
+        assert!(matches!(
+            statements[0].inner(),
+            Statement::FunctionDecl { body: None, .. }
+        ));
+        assert_eq!(
+            parse("enum A { @deprecated }").unwrap_err().message,
+            "Expected variant after attribute"
+        );
+        assert_eq!(
+            parse("@wasm_import(\"env\", \"log\") function log(s: string) {}")
+                .unwrap_err()
+                .message,
+            "Imported functions can't have a body"
+        );
+        // Only the function the attribute is on is imported
+        assert_eq!(
+            parse("@wasm_import(\"env\", \"f\") function f(); function g();")
+                .unwrap_err()
+                .message,
+            "Expected expression"
+        );
+    }
+
+    #[test]
+    fn if_else() {
+        let statements = parse(
+            "function main() {
+                if a == 1 {
+                    one();
+                } else if a == 2 {
+                    two();
+                } else {
+                    other();
+                }
+                let b = if a { 1; } else { 2; };
+            }",
+        )
+        .unwrap();
+        let Statement::FunctionDecl {
+            body: Some(Expression::Block(body, _)),
+            ..
+        } = &statements[0]
+        else {
+            panic!("expected a function");
+        };
+        let Statement::Expression(Expression::If(_, _, Some(otherwise))) = &body[0] else {
+            panic!("expected an if statement");
+        };
+        assert!(matches!(**otherwise, Expression::If(_, _, Some(_))));
+        assert!(matches!(
+            body[1],
+            Statement::VariableDecl {
+                value: Some(Expression::If(_, _, Some(_))),
+                ..
+            }
+        ));
+        assert!(parse("if a { } else b;").is_err());
+
+        // `else if` arms don't count toward how deeply code is nested
+        let arms = (0..100)
+            .map(|i| format!(" else if a == {} {{ f(); }}", i))
+            .collect::<String>();
+        let Statement::Expression(Expression::If(_, _, Some(otherwise))) =
+            &parse(&format!("if a {{}}{} else {{ g(); }}", arms)).unwrap()[0]
+        else {
+            panic!("expected an if statement");
+        };
+        assert!(matches!(**otherwise, Expression::If(_, _, Some(_))));
+        let source = format!("if a {{}}{}", " else if a {}".repeat(1000));
+        assert_eq!(
+            parse(&source).unwrap_err().message,
+            "Too many `else if` branches"
+        );
+    }
+
+    #[test]
+    fn loops() {
+        let statements = parse(
+            "function main() {
+                'outer: for row in rows {
+                    while running {
+                        if done { break 'outer; }
+                        continue;
+                    }
+                }
+                let x = loop { break 5; };
+            }",
+        )
+        .unwrap();
+        let Statement::FunctionDecl {
+            body: Some(Expression::Block(body, _)),
+            ..
+        } = &statements[0]
+        else {
+            panic!("expected a function");
+        };
+        let Statement::Expression(Expression::For(Some(label), item, _, _)) = &body[0] else {
+            panic!("expected a for loop");
+        };
+        assert_eq!(label, "outer");
+        assert!(matches!(&**item, Pattern::Binding(name) if name == "row"));
+        assert!(matches!(
+            &body[1],
+            Statement::VariableDecl {
+                value: Some(Expression::Loop(None, _)),
+                ..
+            }
+        ));
+        for (source, message) in [
+            ("break;", "`break` outside of a loop"),
+            (
+                "loop { function f() { continue; } }",
+                "`continue` outside of a loop",
+            ),
+            ("'a: loop { break 'b; }", "Undeclared label `'b`"),
+            (
+                "while x { break 1; }",
+                "`break` with a value is only allowed in `loop`",
+            ),
+        ] {
+            assert_eq!(parse(source).unwrap_err().message, message);
+        }
+    }
+
+    #[test]
+    fn block_values() {
+        let statements = parse(
+            "function add(a: u8, b: u8) -> u8 { a + b }
+            function abs(x: i8) -> i8 {
+                if x < 0 { return 0 - x; }
+                let y = { x; };
+                x
+            }
+            function nothing() { return; }",
+        )
+        .unwrap();
+        let Statement::FunctionDecl {
+            body: Some(Expression::Block(body, Some(value))),
+            ..
+        } = &statements[0]
+        else {
+            panic!("expected a block with a value");
+        };
+        assert!(body.is_empty());
+        assert!(matches!(**value, Expression::Binary(_, _, _)));
+        let Statement::FunctionDecl {
+            body: Some(Expression::Block(body, Some(_))),
+            ..
+        } = &statements[1]
+        else {
+            panic!("expected a block with a value");
+        };
+        let Statement::Expression(Expression::If(_, then, None)) = &body[0] else {
+            panic!("expected an if statement");
+        };
+        assert!(matches!(
+            &**then,
+            Expression::Block(body, None) if matches!(body[0], Statement::Return(Some(_)))
+        ));
+        assert!(matches!(
+            &body[1],
+            Statement::VariableDecl {
+                value: Some(Expression::Block(_, None)),
+                ..
+            }
+        ));
+        let Statement::FunctionDecl {
+            body: Some(Expression::Block(body, None)),
+            ..
+        } = &statements[2]
+        else {
+            panic!("expected a block without a value");
+        };
+        assert!(matches!(body[0], Statement::Return(None)));
+        assert_eq!(
+            parse("return 1;").unwrap_err().message,
+            "`return` outside of a function"
+        );
+    }
+
+    #[test]
+    fn match_patterns() {
+        let statements = parse(
+            "let size = match shape {
+                Shape.Circle(r) if r > 10 => r,
+                Shape.Rect(w, _) | Shape.Square(w) => { w }
+                Point { x: 0, y, .. } => y,
+                Shape.Empty => 0,
+                1..=9 | -1 => 1,
+                ..0 => 0,
+                n => n,
+            };",
+        )
+        .unwrap();
+        let Statement::VariableDecl {
+            value: Some(Expression::Match(_, arms)),
+            ..
+        } = &statements[0]
+        else {
+            panic!("expected a match");
+        };
+        assert_eq!(arms.len(), 7);
+        assert!(matches!(arms[0].pattern, Pattern::TupleStruct(_, _)));
+        assert!(arms[0].guard.is_some());
+        assert_eq!(arms[1].pattern.bindings(), ["w"]);
+        assert!(matches!(arms[2].pattern, Pattern::Struct(_, _, true)));
+        assert_eq!(arms[2].pattern.bindings(), ["y"]);
+        assert!(matches!(arms[3].pattern, Pattern::Path(_)));
+        let Pattern::Or(alternatives) = &arms[4].pattern else {
+            panic!("expected an or-pattern");
+        };
+        assert!(matches!(
+            alternatives[0],
+            Pattern::Range(Some(_), Some(_), true)
+        ));
+        assert!(matches!(
+            arms[5].pattern,
+            Pattern::Range(None, Some(_), false)
+        ));
+        assert!(matches!(arms[6].pattern, Pattern::Binding(_)));
+        assert_eq!(
+            parse("match x { 1 => a 2 => b }").unwrap_err().message,
+            "Expected comma"
+        );
+        let source = "match x { Bar(a) | Baz(c) => c }";
+        let diagnostic = parse(source).unwrap_err();
+        assert_eq!(diagnostic.message, "`a` isn't bound by every alternative");
+        assert_eq!(&source[diagnostic.span], "Baz(c)");
+        assert!(parse("match x { Bar(a, b) | Baz(b, a) => a }").is_ok());
+    }
+
+    #[test]
+    fn literals() {
+        let statements = parse(
+            "let done = true; let c = 'x'; let n = null; match done { false => n, true => c }",
+        )
+        .unwrap();
+        assert!(matches!(
+            statements[0],
+            Statement::VariableDecl {
+                value: Some(Expression::BoolLiteral(true)),
+                ..
+            }
+        ));
+        assert!(matches!(
+            statements[1],
+            Statement::VariableDecl {
+                value: Some(Expression::CharLiteral('x')),
+                ..
+            }
+        ));
+        assert!(matches!(
+            statements[2],
+            Statement::VariableDecl {
+                value: Some(Expression::NullLiteral),
+                ..
+            }
+        ));
+        let Statement::Expression(Expression::Match(_, arms)) = &statements[3] else {
+            panic!("expected a match");
+        };
+        assert!(matches!(
+            arms[0].pattern,
+            Pattern::Literal(Expression::BoolLiteral(false))
+        ));
+    }
+
+    #[test]
+    fn postfix_chains() {
+        let statements =
+            parse("getUser().name; list[0].render(); (a).b; f()(); Console.log(x);").unwrap();
+        let Statement::Expression(Expression::Member { object, field, .. }) = &statements[0] else {
+            panic!("expected a member access");
+        };
+        assert_eq!(field, "name");
+        assert!(matches!(**object, Expression::Call { .. }));
+        let Statement::Expression(Expression::Call { callee, args, .. }) = &statements[1] else {
+            panic!("expected a call");
+        };
+        assert!(args.is_empty());
+        let Expression::Member { object, .. } = &**callee else {
+            panic!("expected a member access");
+        };
+        assert!(matches!(**object, Expression::Index { .. }));
+        assert!(matches!(
+            &statements[2],
+            Statement::Expression(Expression::Member { object, .. })
+                if matches!(**object, Expression::Identifier(_))
+        ));
+        assert!(matches!(
+            &statements[3],
+            Statement::Expression(Expression::Call { callee, .. })
+                if matches!(**callee, Expression::Call { .. })
+        ));
+        let Statement::Expression(Expression::Call { callee, .. }) = &statements[4] else {
+            panic!("expected a call");
+        };
+        assert!(callee.is_path());
+        assert_eq!(
+            parse("list[0;").unwrap_err().message,
+            "Expected closing bracket"
+        );
+
+        // Long chains don't count toward how deeply code is nested
+        let then = (0..100)
+            .map(|i| format!(".then(f{})", i))
+            .collect::<String>();
+        let source = format!(
+            "class A {{ function f() {{ {{ {{ if a {{ fetch(url){}; }} }} }} }} }}",
+            then
+        );
+        assert!(parse(&source).is_ok());
+        let source = format!("a{};", ".b".repeat(1000));
+        assert_eq!(parse(&source).unwrap_err().message, "Chain is too long");
+    }
+
+    #[test]
+    fn collection_literals() {
+        let statements = parse(
+            "let xs = [1, 2, ...rest,]; let user = { name: \"Ann\", \"last name\": [], }; let b = { a }; let e = {};",
+        )
+        .unwrap();
+        let Statement::VariableDecl {
+            value: Some(Expression::Array(items)),
+            ..
+        } = &statements[0]
+        else {
+            panic!("expected an array");
+        };
+        assert_eq!(items.len(), 3);
+        assert!(matches!(items[2], Expression::Spread(_)));
+        let Statement::VariableDecl {
+            value: Some(Expression::Map(entries)),
+            ..
+        } = &statements[1]
+        else {
+            panic!("expected a map");
+        };
+        assert_eq!(entries[0].0, "name");
+        assert_eq!(entries[1].0, "last name");
+        assert!(matches!(entries[1].1, Expression::Array(_)));
+        assert!(matches!(
+            statements[2],
+            Statement::VariableDecl {
+                value: Some(Expression::Block(_, _)),
+                ..
+            }
+        ));
+        assert!(matches!(
+            statements[3],
+            Statement::VariableDecl {
+                value: Some(Expression::Block(_, None)),
+                ..
+            }
+        ));
+        assert_eq!(
+            parse("let m = { a: 1 b };").unwrap_err().message,
+            "Expected comma"
+        );
+        assert_eq!(
+            parse("let m = { a: 1;").unwrap_err().message,
+            "Expected closing brace"
+        );
+        assert_eq!(fix("let xs = [1 2];"), "let xs = [1, 2];");
+        assert_eq!(parse("[...];").unwrap_err().message, "Expected expression");
+    }
+
+    #[test]
+    fn lambdas() {
+        let statements = parse(
+            "let double = (x: u8) -> x * factor; button.onClick(() -> Console.log(label)); \
+             let f = function(a, b) -> u8 { let c = a; (d) -> c(d, b, e) };",
+        )
+        .unwrap();
+        let Statement::VariableDecl {
+            value: Some(Expression::Lambda {
+                params, captures, ..
+            }),
+            ..
+        } = &statements[0]
+        else {
+            panic!("expected a lambda");
+        };
+        assert!(matches!(params[..], [(_, Some(_))]));
+        assert_eq!(captures, &["factor"]);
+        let Statement::Expression(Expression::Call { args, .. }) = &statements[1] else {
+            panic!("expected a call");
+        };
+        let Expression::Lambda {
+            params, captures, ..
+        } = &args[0]
+        else {
+            panic!("expected a lambda");
+        };
+        assert!(params.is_empty());
+        assert_eq!(captures, &["Console", "label"]);
+        let Statement::VariableDecl {
+            value:
+                Some(Expression::Lambda {
+                    return_type,
+                    captures,
+                    ..
+                }),
+            ..
+        } = &statements[2]
+        else {
+            panic!("expected a lambda");
+        };
+        assert!(return_type.is_some());
+        assert_eq!(captures, &["e"]);
+        assert!(parse("let g = (a) + 1;").is_ok());
+        assert_eq!(
+            parse("let h = (x) -> { return x; };").map(|s| s.len()),
+            Ok(1)
+        );
+        // `super` calls need the receiver as much as `self` ones
+        let statements =
+            parse("class B extends A { function f() { let g = () -> super.g(); } }").unwrap();
+        let Statement::ClassDecl { members, .. } = &statements[0] else {
+            panic!("expected a class");
+        };
+        let Statement::FunctionDecl {
+            body: Some(Expression::Block(body, _)),
+            ..
+        } = &members[0]
+        else {
+            panic!("expected a method");
+        };
+        let Statement::VariableDecl {
+            value: Some(Expression::Lambda { captures, .. }),
+            ..
+        } = &body[0]
+        else {
+            panic!("expected a lambda");
+        };
+        assert_eq!(captures, &["self"]);
+        // Captures are worked out while parsing, so a body too long to walk is an error
+        let source = format!("let f = () -> a{};", " + a".repeat(20_000));
+        assert_eq!(
+            parse(&source).unwrap_err().message,
+            "Expression is too long"
+        );
+    }
+
+    #[test]
+    fn types() {
+        let statements = parse(
+            "let a: List<Map<string, u8>>= x; let b: [Foo.Bar]?; let c: (u8, string); \
+             let d: ((u8) -> bool)?; function f(g: (u8, string) -> bool) -> () {}",
+        )
+        .unwrap();
+        let Statement::VariableDecl {
+            var_type: Some(Type::Named(path, args)),
+            value: Some(_),
+            ..
+        } = &statements[0]
+        else {
+            panic!("expected a generic type");
+        };
+        assert_eq!(path, &["List"]);
+        assert!(matches!(&args[..], [Type::Named(_, inner)] if inner.len() == 2));
+        let Statement::VariableDecl {
+            var_type: Some(Type::Optional(array)),
+            value: None,
+            ..
+        } = &statements[1]
+        else {
+            panic!("expected an optional type");
+        };
+        assert!(
+            matches!(&**array, Type::Array(element) if matches!(&**element, Type::Named(path, _) if path.len() == 2))
+        );
+        assert!(matches!(
+            &statements[2],
+            Statement::VariableDecl { var_type: Some(Type::Tuple(elements)), .. } if elements.len() == 2
+        ));
+        let Statement::VariableDecl {
+            var_type: Some(Type::Optional(function)),
+            ..
+        } = &statements[3]
+        else {
+            panic!("expected an optional type");
+        };
+        assert!(matches!(&**function, Type::Function(params, _) if params.len() == 1));
+        let Statement::FunctionDecl {
+            return_type: Some(Type::Tuple(unit)),
+            params,
+            ..
+        } = &statements[4]
+        else {
+            panic!("expected a function");
+        };
+        assert!(unit.is_empty());
+        assert!(matches!(params[0].1, Type::Function(_, _)));
+        assert_eq!(parse("let x: 1 + 2;").unwrap_err().message, "Expected type");
+        let diagnostic = parse("let x: List<u8;").unwrap_err();
+        assert_eq!(diagnostic.suggestions[0].replacement, ">");
+    }
+
+    #[test]
+    fn generics() {
+        let statements = parse(
+            "function map<T, U>(xs: List<T>, f: (T) -> U) -> List<U> {} \
+             class Box<T: Comparable + Hashable> { let value: T; } \
+             let a = parse<List<u8>>(s); let b = a < b; let c = x < (y > (z));",
+        )
+        .unwrap();
+        let Statement::FunctionDecl { type_params, .. } = &statements[0] else {
+            panic!("expected a function");
+        };
+        let names = type_params
+            .iter()
+            .map(|p| p.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["T", "U"]);
+        let Statement::ClassDecl { type_params, .. } = &statements[1] else {
+            panic!("expected a class");
+        };
+        assert_eq!(type_params[0].bounds.len(), 2);
+        assert!(matches!(
+            &statements[2],
+            Statement::VariableDecl { value: Some(Expression::Call { type_args, .. }), .. }
+                if matches!(&type_args[..], [Type::Named(_, args)] if args.len() == 1)
+        ));
+        for statement in &statements[3..5] {
+            assert!(matches!(
+                statement,
+                Statement::VariableDecl {
+                    value: Some(Expression::Binary(_, Token::Lt, _)),
+                    ..
+                }
+            ));
+        }
+    }
+
+    #[test]
+    fn inheritance() {
+        let statements = parse(
+            "interface Clickable extends Focusable, Hoverable { function click(x: u8) -> bool; function hover() {} } \
+             abstract class Widget { abstract function render(); function show() { render(); } } \
+             class Button extends Widget implements Clickable { function render() { super.show(); } }",
+        )
+        .unwrap();
+        let Statement::InterfaceDecl {
+            extends, members, ..
+        } = &statements[0]
+        else {
+            panic!("expected an interface");
+        };
+        assert_eq!(extends.len(), 2);
+        assert!(matches!(
+            members[0],
+            Statement::FunctionDecl { body: None, .. }
+        ));
+        assert!(matches!(
+            members[1],
+            Statement::FunctionDecl { body: Some(_), .. }
+        ));
+        let Statement::ClassDecl {
+            is_abstract: true,
+            members,
+            ..
+        } = &statements[1]
+        else {
+            panic!("expected an abstract class");
+        };
+        assert!(matches!(
+            members[0],
+            Statement::FunctionDecl { body: None, .. }
+        ));
+        let Statement::ClassDecl {
+            extends: Some(Type::Named(base, _)),
+            implements,
+            ..
+        } = &statements[2]
+        else {
+            panic!("expected a subclass");
+        };
+        assert_eq!(base, &["Widget"]);
+        assert_eq!(implements.len(), 1);
+
+        for (source, message) in [
+            (
+                "class A { abstract function f(); }",
+                "Only methods of abstract classes can be abstract",
+            ),
+            (
+                "abstract class A { abstract function f() {} }",
+                "Abstract methods can't have a body",
+            ),
+            (
+                "abstract class A { let x = { abstract function f(); 1 }; }",
+                "Only methods of abstract classes can be abstract",
+            ),
+            (
+                "class A { function f() { super.f(); } }",
+                "`super` outside of a class that extends another",
+            ),
+            (
+                "interface A { let x = 1; }",
+                "Only functions can be members of interfaces",
+            ),
+            ("function f();", "Expected expression"),
+        ] {
+            assert_eq!(parse(source).unwrap_err().message, message, "{}", source);
+        }
+    }
+
+    #[test]
+    fn constructors() {
+        let statements = parse(include_str!("example.owl")).unwrap();
+        let Statement::ClassDecl { members, .. } = &statements[2] else {
+            panic!("expected a class");
+        };
+        assert!(
+            matches!(members[0], Statement::ConstructorDecl(ref params, _) if params.len() == 1)
+        );
+        assert!(matches!(
+            &members[1],
+            Statement::Modified(modifiers, function)
+                if modifiers.is_static && matches!(**function, Statement::FunctionDecl { .. })
+        ));
+        let Statement::FunctionDecl {
+            body: Some(Expression::Block(body, _)),
+            ..
+        } = &members[1].inner()
+        else {
+            panic!("expected a function");
+        };
+        assert!(matches!(
+            &body[0],
+            Statement::Return(Some(Expression::New { args, .. })) if args.len() == 1
+        ));
+
+        for (source, message) in [
+            (
+                "class A { static function f() { self } }",
+                "`self` outside of an instance method",
+            ),
+            (
+                "function f() { self }",
+                "`self` outside of an instance method",
+            ),
+            (
+                "constructor() {}",
+                "Constructors are only allowed in classes",
+            ),
+            (
+                "class A { constructor() {} constructor() {} }",
+                "A class can only have one constructor",
+            ),
+            (
+                "static function f() {}",
+                "Only members of classes can be static",
+            ),
+            ("let a = new [u8]();", "Expected class name"),
+        ] {
+            assert_eq!(parse(source).unwrap_err().message, message, "{}", source);
+        }
+        // Lambdas in methods can use `self`
+        assert!(parse("class A { function f() { () -> self } }").is_ok());
+        // A static field doesn't make the methods after it static
+        assert!(parse(
+            "class A { function f() { class B { static let x = 1; function g() { self } } } }"
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn visibility() {
+        let widget = Module {
+            path: vec!["ui".to_string(), "widget".to_string()],
+            statements: parse(
+                "pub class Widget { private let id: u8; pub function show() {} } \
+                 class Registry {} pub let version = 1; function helper() {}",
+            )
+            .unwrap(),
+        };
+        let source = "import ui.widget.{Widget, Widget.show, Widget.id, Registry, helpr, version}, Console.log;";
+        let app = Module {
+            path: vec!["app".to_string()],
+            statements: parse(source).unwrap(),
+        };
+        let modules = [widget, app];
+        let diagnostics = check_imports(&modules[1], &modules);
+        let messages = diagnostics
+            .iter()
+            .map(|d| d.message.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            [
+                "`id` is a private member of `Widget`",
+                "`Registry` is private to `ui.widget`",
+                "`helpr` not found in `ui.widget`",
+            ]
+        );
+        let span = diagnostics[0].span.clone();
+        assert_eq!(
+            source
+                .chars()
+                .skip(span.start)
+                .take(span.len())
+                .collect::<String>(),
+            "Widget.id"
+        );
+        assert_eq!(
+            diagnostics[2].suggestions[0].replacement,
+            "ui.widget.helper"
+        );
+        assert!(check_imports(&modules[0], &modules).is_empty());
+
+        for (source, message) in [
+            (
+                "function f() { pub let x = 1; }",
+                "Only top-level declarations and class members can have a visibility",
+            ),
+            (
+                "interface I { pub function f(); }",
+                "Only top-level declarations and class members can have a visibility",
+            ),
+            ("pub private let x = 1;", "Duplicate visibility modifier"),
+            ("pub import Foo;", "Only declarations can have a visibility"),
+            (
+                "private class A {}",
+                "Top-level declarations are already private unless they're `pub`",
+            ),
+            (
+                "class A { private constructor() {} }",
+                "Constructors can't have a visibility",
+            ),
+        ] {
+            assert_eq!(parse(source).unwrap_err().message, message, "{}", source);
+        }
+        assert_eq!(fix("private let x = 1;"), "let x = 1;");
+    }
+
+    #[test]
+    fn enums() {
+        let statements = parse(
+            "pub enum Shape { Circle(radius: f64), Rect(w: f64, h: f64), Empty } \
+             enum Color { Red = 1, Green = -2, Blue, }",
+        )
+        .unwrap();
+        let Statement::EnumDecl { name, variants, .. } = statements[0].inner() else {
+            panic!("{:?}", statements[0]);
+        };
+        assert_eq!(name, "Shape");
+        assert_eq!(
+            variants
+                .iter()
+                .map(|v| (v.name.as_str(), v.fields.len()))
+                .collect::<Vec<_>>(),
+            [("Circle", 1), ("Rect", 2), ("Empty", 0)]
+        );
+        let Statement::EnumDecl { variants, .. } = &statements[1] else {
+            panic!("{:?}", statements[1]);
+        };
+        assert!(matches!(
+            variants[1].discriminant,
+            Some(Expression::SignedLiteral(-2))
+        ));
+        assert!(variants[2].discriminant.is_none());
+
+        let statements = parse(
+            "let s = Shape.Circle(1.0); \
+             let area = match s { Shape.Circle(r) => r * r, Shape.Rect { w, h } => w * h, Shape.Empty => 0.0 };",
+        )
+        .unwrap();
+        assert!(matches!(
+            &statements[0],
+            Statement::VariableDecl { value: Some(Expression::Call { callee, .. }), .. }
+                if matches!(&**callee, Expression::Member { field, .. } if field == "Circle")
+        ));
+
+        let shapes = Module {
+            path: vec!["shapes".to_string()],
+            statements: parse("pub enum Shape { Circle(radius: f64), Empty }").unwrap(),
+        };
+        let app = Module {
+            path: vec!["app".to_string()],
+            statements: parse("import shapes.Shape.Circle, shapes.Shape.Square;").unwrap(),
+        };
+        let modules = [shapes, app];
+        let diagnostics = check_imports(&modules[1], &modules);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "`Shape` has no member `Square`");
+
+        for (source, message) in [
+            ("enum A { X, X }", "Duplicate variant `X`"),
+            (
+                "enum A { X(a: u8), Y = 1 }",
+                "Only enums without fields can have discriminants",
+            ),
+            ("enum A { X = \"a\" }", "Expected integer discriminant"),
+            ("enum A { X Y }", "Expected closing brace"),
+            ("static enum A {}", "Only members of classes can be static"),
+        ] {
+            assert_eq!(parse(source).unwrap_err().message, message, "{}", source);
+        }
+    }
+
+    #[test]
+    fn imports() {
+        let statements = parse(
+            "import Foo.Bar as Baz, Foo.*, Foo.{Bar.{A, B as C}, *}, \"./widget.js\" as Widget;",
+        )
+        .unwrap();
+        let Statement::Import(imports) = &statements[0] else {
+            panic!("{:?}", statements[0]);
+        };
+        assert_eq!(
+            imports.iter().map(Import::names).collect::<Vec<_>>(),
+            [vec!["Baz"], vec![], vec!["A", "C"], vec!["Widget"]]
+        );
+        let Import::Tree { tree, .. } = &imports[2] else {
+            panic!("{:?}", imports[2]);
+        };
+        assert_eq!(
+            tree.leaves()
+                .iter()
+                .map(|(path, _)| path.join("."))
+                .collect::<Vec<_>>(),
+            ["Foo.Bar.A", "Foo.Bar.B", "Foo"]
+        );
+        assert!(matches!(
+            &imports[3],
+            Import::Module { source, alias, .. } if source == "./widget.js" && alias == "Widget"
+        ));
+
+        let widget = Module {
+            path: vec!["ui".to_string(), "widget".to_string()],
+            statements: parse("pub class Widget { private let id: u8; } class Registry {}")
+                .unwrap(),
+        };
+        let app = Module {
+            path: vec!["ui".to_string(), "app".to_string()],
+            statements: parse(
+                "import .widget.{Widget as W, Registry}, .widgets.*, ui.widget.*, ui.*, \
+                 ui.widget.Widget.*, \"./native.js\" as Native;",
+            )
+            .unwrap(),
+        };
+        let modules = [widget, app];
+        let messages = check_imports(&modules[1], &modules)
+            .into_iter()
+            .map(|d| d.message)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            [
+                "`Registry` is private to `ui.widget`",
+                "`widgets` not found in the current package",
+            ]
+        );
+
+        for (source, message) in [
+            (
+                "import \"./widget.js\";",
+                "JavaScript modules must be imported with `as`",
+            ),
+            ("import *;", "Expected identifier"),
+            ("import Foo.{Bar as};", "Expected identifier"),
+            ("import Foo.* as Bar;", "Expected semicolon"),
+        ] {
+            assert_eq!(parse(source).unwrap_err().message, message, "{}", source);
+        }
+    }
+
+    #[test]
+    fn mutability() {
+        let source = "const LIMIT: u8 = 2 * SIZE; const SIZE = 8; let mut count = 0; let total = 0;
+        class Counter {
+            let step: u8;
+            let mut value: u8 = 0;
+            static const MAX = 10;
+            constructor(step: u8) { self.step = step; }
+            function tick() {
+                self.value += self.step;
+                self.step = 2;
+                Counter.MAX = 11;
+            }
+        }
+        function main(n: u8) {
+            count += 1;
+            total = 1;
+            LIMIT = 3;
+            n = 2;
+            let x: u8;
+            if n > 1 { x = 1; } else { x = 2; }
+            x = 3;
+            for i in items { i = 0; }
+            let mut y = 1;
+            y = 2;
+            const Z = f();
+        }";
+        let statements = parse(source).unwrap();
+        assert!(matches!(
+            statements[2],
+            Statement::VariableDecl {
+                mutability: Mutability::Mutable,
+                value: Some(_),
+                ..
+            }
+        ));
+        let diagnostics = check_assignments(&statements);
+        let messages = diagnostics
+            .iter()
+            .map(|d| d.message.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            [
+                "Cannot assign to immutable field `step`",
+                "Cannot assign to constant `MAX`",
+                "Cannot assign twice to immutable variable `total`",
+                "Cannot assign to constant `LIMIT`",
+                "Cannot assign to immutable binding `n`",
+                "Cannot assign twice to immutable variable `x`",
+                "Cannot assign to immutable binding `i`",
+                "Constants must be known at compile time",
+            ]
+        );
+        let fixed = apply_fixes(source, &diagnostics[2..3]);
+        assert!(fixed.contains("let mut total = 0;"));
+
+        // Loops and functions may run more than once, so they can't initialize a variable
+        for body in [
+            "while c { x = 1; }",
+            "loop { x = 1; }",
+            "for i in xs { x = i; }",
+            "let g = () -> { x = 1; }; g(); g();",
+        ] {
+            let source = format!("function f() {{ let x: u8; {} }}", body);
+            let diagnostics = check_assignments(&parse(&source).unwrap());
+            assert_eq!(diagnostics.len(), 1, "{}", source);
+            assert_eq!(
+                diagnostics[0].message,
+                "Cannot assign twice to immutable variable `x`"
+            );
+        }
+        let source = "function f() { while c { let x: u8; x = 1; } }";
+        assert!(check_assignments(&parse(source).unwrap()).is_empty());
+
+        // Constructors initialize immutable fields once, like variables
+        for body in [
+            "self.x = 1; self.x = 2;",
+            "while c { self.x = 1; }",
+            "let g = () -> { self.x = 1; };",
+        ] {
+            let source = format!("class A {{ let x: u8; constructor() {{ {} }} }}", body);
+            let diagnostics = check_assignments(&parse(&source).unwrap());
+            assert_eq!(diagnostics.len(), 1, "{}", source);
+            assert_eq!(
+                diagnostics[0].message,
+                "Cannot assign twice to immutable field `x`"
+            );
+        }
+        let source = "class A { let x: u8 = 0; constructor() { self.x = 1; } }";
+        assert_eq!(check_assignments(&parse(source).unwrap()).len(), 1);
+        let source = "class A { let x: u8; constructor() { if c { self.x = 1; } else { self.x = 2; } } } \
+                      class B extends A { let mut x: u8; constructor() { self.x = 1; self.x = 2; } }";
+        assert!(check_assignments(&parse(source).unwrap()).is_empty());
+
+        for (source, message) in [
+            ("const X: u8;", "Constants must have a value"),
+            ("const mut X = 1;", "Expected pattern"),
+        ] {
+            assert_eq!(parse(source).unwrap_err().message, message, "{}", source);
+        }
+    }
+
+    #[test]
+    fn destructuring() {
+        let source = "let (x, y) = point;
+        let [first, ...rest] = items;
+        let { name, age: years, .. } = user;
+        function area((w, h): (u8, u8)) { w * h }
+        for (key, value) in entries {}
+        let sum = ([a, b]) -> a + b;";
+        let statements = parse(source).unwrap();
+        let bindings = statements
+            .iter()
+            .map(|statement| match statement {
+                Statement::VariableDecl { pattern, .. } => pattern.bindings(),
+                Statement::FunctionDecl { params, .. } => params[0].0.bindings(),
+                Statement::Expression(Expression::For(_, pattern, ..)) => pattern.bindings(),
+                _ => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            bindings,
+            [
+                vec!["x", "y"],
+                vec!["first", "rest"],
+                vec!["name", "years"],
+                vec!["w", "h"],
+                vec!["key", "value"],
+                vec!["sum"],
+            ]
+        );
+        let Statement::VariableDecl {
+            value: Some(Expression::Lambda { params, .. }),
+            ..
+        } = &statements[5]
+        else {
+            unreachable!()
+        };
+        assert_eq!(params[0].0.bindings(), ["a", "b"]);
+
+        assert!(parse("let Point { x, y } = p;").is_ok());
+        let diagnostics = check_assignments(&parse("let (a, b) = c; a = 1;").unwrap());
+        assert_eq!(
+            diagnostics[0].message,
+            "Cannot assign twice to immutable variable `a`"
+        );
+
+        for (source, message) in [
+            (
+                "let Shape.Circle(r) = s;",
+                "Refutable pattern in `let`, use `match` instead",
+            ),
+            (
+                "let Shape.Rect { w, h } = s;",
+                "Refutable pattern in `let`, use `match` instead",
+            ),
+            (
+                "function f(1: u8) {}",
+                "Refutable pattern in parameters, use `match` instead",
+            ),
+            (
+                "for 0 in xs {}",
+                "Refutable pattern in `for` loops, use `match` instead",
+            ),
+            (
+                "let [a, ...b, ...c] = d;",
+                "Only one rest pattern is allowed in an array",
+            ),
+            (
+                "class A { let (a, b) = c; }",
+                "Fields can't be destructured",
+            ),
+        ] {
+            assert_eq!(parse(source).unwrap_err().message, message, "{}", source);
+        }
+    }
+
+    #[test]
+    fn operators() {
+        let tokens = Lexer::new("a<<=b>>c ?? d?.e x-1 (-1")
+            .map(|t| t.unwrap().0)
+            .collect::<Vec<_>>();
+        assert_eq!(tokens[1], Token::ShlAssign);
+        assert_eq!(tokens[3], Token::Shr);
+        assert_eq!(tokens[5], Token::Coalesce);
+        assert_eq!(tokens[7], Token::QuestionDot);
+        // Signs are left to the parser, as the lexer can't tell them from subtractions
+        assert_eq!(tokens[10], Token::Sub);
+        assert_eq!(tokens[13], Token::Sub);
+        assert_eq!(tokens[14], Token::UnsignedLiteral(1));
+
+        let statements = parse(
+            "a + b * c - d;
+            2 ** 3 ** 2;
+            a || b && c == d;
+            for i in 0..=n {}
+            let label = done ? \"done\" : count > 0 ? \"busy\" : \"idle\";
+            let name = user?.profile.name ?? \"anonymous\";
+            let a = -2 ** 2;
+            let b = -x ** 2;
+            let c = if a {1} else {2}-1;
+            let d = {a: 1}-1;",
+        )
+        .unwrap();
+        let Statement::Expression(Expression::Binary(left, Token::Sub, _)) = &statements[0] else {
+            panic!("expected a subtraction");
+        };
+        assert!(matches!(
+            &**left,
+            Expression::Binary(_, Token::Add, right) if matches!(**right, Expression::Binary(_, Token::Mul, _))
+        ));
+        assert!(matches!(
+            &statements[1],
+            Statement::Expression(Expression::Binary(_, Token::Pow, right))
+                if matches!(**right, Expression::Binary(_, Token::Pow, _))
+        ));
+        assert!(matches!(
+            &statements[2],
+            Statement::Expression(Expression::Binary(_, Token::Or, right))
+                if matches!(**right, Expression::Binary(_, Token::And, _))
+        ));
+        assert!(matches!(
+            &statements[3],
+            Statement::Expression(Expression::For(_, _, range, _))
+                if matches!(**range, Expression::Range { inclusive: true, .. })
+        ));
+        let Statement::VariableDecl {
+            value: Some(Expression::Conditional(_, _, otherwise)),
+            ..
+        } = &statements[4]
+        else {
+            panic!("expected a conditional");
+        };
+        assert!(matches!(**otherwise, Expression::Conditional(..)));
+        let Statement::VariableDecl {
+            value: Some(Expression::Coalesce(value, _)),
+            ..
+        } = &statements[5]
+        else {
+            panic!("expected `??`");
+        };
+        assert!(value.is_optional_chain());
+        assert!(matches!(
+            &**value,
+            Expression::Member { optional: false, object, .. }
+                if matches!(**object, Expression::Member { optional: true, .. })
+        ));
+        // The sign of a literal binds as tightly as the sign of a variable
+        assert!(matches!(
+            &statements[6],
+            Statement::VariableDecl { value: Some(Expression::Binary(left, Token::Pow, _)), .. }
+                if matches!(**left, Expression::SignedLiteral(-2))
+        ));
+        assert!(matches!(
+            &statements[7],
+            Statement::VariableDecl { value: Some(Expression::Binary(left, Token::Pow, _)), .. }
+                if matches!(**left, Expression::Unary(Token::Sub, _))
+        ));
+        assert!(parse("match x { -9223372036854775808..=-1 => 0, _ => 1 };").is_ok());
+        // A `-` after a block-like expression subtracts
+        for statement in &statements[8..] {
+            assert!(matches!(
+                statement,
+                Statement::VariableDecl {
+                    value: Some(Expression::Binary(_, Token::Sub, _)),
+                    ..
+                }
+            ));
+        }
+
+        for (source, message) in [
+            ("let x = a ? b;", "Expected colon"),
+            ("let x = 0..;", "Expected end of range"),
+            ("user?.name = x;", "Can't assign to an optional chain"),
+            ("-9223372036854775809;", "Integer literal is too small"),
+        ] {
+            assert_eq!(parse(source).unwrap_err().message, message, "{}", source);
+        }
+
+        // Operators are folded in a loop, but the tree they build is as deep as the chain
+        assert!(parse(&format!("let x = a{};", " + a".repeat(255))).is_ok());
+        let source = format!("let x = a{};", " + a".repeat(5000));
+        assert_eq!(
+            parse(&source).unwrap_err().message,
+            "Expression is too long"
+        );
+        // Parentheses don't start a new chain, since the operators around them nest the
+        // tree further
+        let source = format!("let x = (a{}){};", " && a".repeat(200), " || a".repeat(100));
+        assert_eq!(
+            parse(&source).unwrap_err().message,
+            "Expression is too long"
+        );
+        let source = format!("(a{}){};", ".b".repeat(200), ".c".repeat(100));
+        assert_eq!(parse(&source).unwrap_err().message, "Chain is too long");
+    }
+
+    #[test]
+    fn error_handling() {
+        let source = "function load(path: String) -> u8 {
+            let port: u8;
+            let value = try {
+                port = read(path);
+                port
+            } catch ({ message }) {
+                port = 80;
+                log(message);
+                0
+            } finally {
+                close(path);
+            };
+            if value == 0 { throw new Error(\"empty\"); }
+            value
+        }";
+        let statements = parse(source).unwrap();
+        let Statement::FunctionDecl {
+            body: Some(Expression::Block(body, _)),
+            ..
+        } = &statements[0]
+        else {
+            panic!("expected a function");
+        };
+        let Statement::VariableDecl {
+            value: Some(Expression::Try { catch, finally, .. }),
+            ..
+        } = &body[1]
+        else {
+            panic!("expected a try");
+        };
+        let catch = catch.as_ref().unwrap();
+        assert_eq!(catch.pattern.as_ref().unwrap().bindings(), ["message"]);
+        assert!(finally.is_some());
+        let Statement::Expression(Expression::If(_, then, _)) = &body[2] else {
+            panic!("expected an if");
+        };
+        assert!(matches!(
+            &**then,
+            Expression::Block(statements, _)
+                if matches!(statements[0], Statement::Expression(Expression::Throw(_)))
+        ));
+        let diagnostics = check_assignments(&statements);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "Cannot assign twice to immutable variable `port`"
+        );
+        // Keywords are still names after a dot and as map keys, like in JavaScript
+        let statements = parse(
+            "fetch(url).then(f).catch(g).finally(h); let c = obj?.constructor; \
+             let m = { new: 1, static: e.static };",
+        )
+        .unwrap();
+        let Statement::Expression(Expression::Call { callee, .. }) = &statements[0] else {
+            panic!("expected a call");
+        };
+        assert!(matches!(&**callee, Expression::Member { field, .. } if field == "finally"));
+        assert!(matches!(
+            &statements[1],
+            Statement::VariableDecl { value: Some(Expression::Member { field, optional: true, .. }), .. }
+                if field == "constructor"
+        ));
+        let Statement::VariableDecl {
+            value: Some(Expression::Map(entries)),
+            ..
+        } = &statements[2]
+        else {
+            panic!("expected a map");
+        };
+        assert_eq!(entries[0].0, "new");
+        assert!(matches!(&entries[1].1, Expression::Member { field, .. } if field == "static"));
+
+        for (source, message) in [
+            (
+                "try { f(); }",
+                "Expected `catch` or `finally` after `try` block",
+            ),
+            ("catch (e) {}", "`catch` without a `try`"),
+            (
+                "try {} catch (e) {} finally {} finally {}",
+                "`finally` without a `try`",
+            ),
+            ("throw;", "Expected error to throw"),
+            (
+                "function f() { try {} finally { return; } }",
+                "`return` can't leave a `finally` block",
+            ),
+            (
+                "'outer: for x in xs { try {} finally { continue 'outer; } }",
+                "`continue` can't leave a `finally` block",
+            ),
+        ] {
+            assert_eq!(parse(source).unwrap_err().message, message, "{}", source);
+        }
+        // Jumps that stay inside of the finally block are fine
+        parse("loop { try {} finally { for x in xs { break; } f(() -> { return; }); } }").unwrap();
+    }
+
+    /// Feeds `source` through every stage, none of which may panic whatever the input.
+    fn exercise(source: &str) {
+        match parse(source) {
+            Ok(statements) => {
+                check(&statements, &LintLevels::default());
+                check_assignments(&statements);
+                let modules = [Module {
+                    path: vec!["main".to_string()],
+                    statements,
+                }];
+                check_imports(&modules[0], &modules);
+            }
+            Err(diagnostic) => {
+                PlainRenderer.render(source, &diagnostic);
+                HtmlRenderer.render(source, &diagnostic);
+                apply_fixes(source, std::slice::from_ref(&diagnostic));
+                // Fixes may only ever get parsing further
+                let fixed = fix(source);
+                if let Err(after) = parse(&fixed) {
+                    assert!(
+                        unparsed(&fixed, &after) <= unparsed(source, &diagnostic),
+                        "{}",
+                        source
+                    );
+                }
+            }
+        }
+    }
+
+    /// A xorshift generator, so the inputs are random but reproducible.
+    struct Rng(u64);
+    impl Rng {
+        fn below(&mut self, n: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % n.max(1) as u64) as usize
+        }
+    }
+
+    #[test]
+    fn no_panics() {
+        for source in [
+            "",
+            "99999999999999999999",
+            "-9223372036854775808;",
+            "-9223372036854775809",
+            "1.5 + -2.25;",
+            "\"\\u",
+            "\"\\uZZZZ\"",
+            "\"\\uD800\"",
+            "\"abc",
+            "'",
+            "'ab'",
+            "/* unterminated",
+            "@",
+            "@allow(",
+            "Foo.",
+            "Foo..Bar",
+            "import",
+            "import Foo.{",
+            "let",
+            "let x: 1 = 2;",
+            "function",
+            "function f(",
+            "class",
+            "class A { class B {} }",
+            "(",
+            ")",
+            "}",
+            "exit(",
+            "a.(",
+            "a[",
+            "pub",
+            "private static",
+            "new",
+            "new Foo",
+            "class A { static",
+            "class A { constructor",
+            "abstract",
+            "abstract class",
+            "interface I extends",
+            "class A extends B implements {",
+            "super",
+            "function f<",
+            "class A<T: > {}",
+            "f<u8>(",
+            "let x: List<",
+            "let x: (u8,) ->",
+            "let x: [",
+            "let x: u8?? = 1;",
+            "() ->",
+            "function(",
+            "(a, ) -> (",
+            "[...",
+            "{a:",
+            "{\"a\": 1 b",
+            "enum",
+            "enum A { X(",
+            "enum A { X = ",
+            "pub enum A<T> { X(a: T), }",
+            "import .",
+            "import Foo.{Bar.{",
+            "import \"a.js\" as",
+            "import Foo as",
+            "enum A { @",
+            "@deprecated(",
+            "let mut",
+            "const X =",
+            "let (",
+            "let [...a, ...b] = x;",
+            "let { a: } = b;",
+            "for (a in",
+            "function f([a",
+            "a ? b :",
+            "a ?? ",
+            "a?.",
+            "0..=",
+            "1 << ",
+            "let x: u8?? =",
+            "try",
+            "try {} catch (",
+            "try {} finally",
+            "throw throw",
+        ] {
+            exercise(source);
+        }
+        exercise(&"(".repeat(10_000));
+        exercise(&"{".repeat(10_000));
+        exercise(&"!".repeat(10_000));
+        exercise(&format!("a{}", "()".repeat(10_000)));
+        exercise(&format!("a{};", ".b".repeat(255)));
+        exercise(&"a.b(".repeat(10_000));
+        exercise(&format!("a{};", " + a".repeat(255)));
+        exercise(&format!("a{};", " + a".repeat(10_000)));
+        exercise(&format!("a{};", " ?? a".repeat(10_000)));
+        exercise(&format!("a{};", " && a".repeat(100_000)));
+        exercise(&format!("let f = () -> a{};", " && a".repeat(20_000)));
+        exercise(&format!("let x: {}", "[".repeat(10_000)));
+        exercise(&" ".repeat(100_000));
+        assert_eq!(fix(&"(".repeat(10_000)).len(), 10_000);
+
+        let alphabet = "(){}[]+-*/%&|^!=<>,;.:@\"'\\_ \n\tfnlet0129é"
+            .chars()
+            .collect::<Vec<_>>();
+        let example = include_str!("example.owl").chars().collect::<Vec<_>>();
+        let mut rng = Rng(0x5eed);
+        for _ in 0..2000 {
+            let len = rng.below(40);
+            let source = (0..len)
+                .map(|_| alphabet[rng.below(alphabet.len())])
+                .collect::<String>();
+            exercise(&source);
+        }
+        for _ in 0..2000 {
+            let mut source = example.clone();
+            for _ in 0..1 + rng.below(4) {
+                let at = rng.below(source.len());
+                match rng.below(3) {
+                    0 => {
+                        let end = (at + rng.below(8)).min(source.len());
+                        source.drain(at..end);
+                    }
+                    1 => source.insert(at, alphabet[rng.below(alphabet.len())]),
+                    _ => {
+                        let end = (at + rng.below(8)).min(source.len());
+                        let copy = source[at..end].to_vec();
+                        source.splice(at..at, copy);
+                    }
+                }
+            }
+            exercise(&source.into_iter().collect::<String>());
+        }
+    }
 }
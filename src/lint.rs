@@ -0,0 +1,470 @@
+use crate::error::{closest_match, Applicability, Diagnostic, Level};
+use crate::parser::{Attribute, Expression, Pattern, Statement};
+use std::collections::HashMap;
+use std::ops::{Range, RangeInclusive};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum LintLevel {
+    Allow,
+    Warn,
+    Deny,
+}
+impl LintLevel {
+    /// The level set by an attribute like `@allow(...)`, if `name` is one of them.
+    pub fn from_attribute(name: &str) -> Option<LintLevel> {
+        match name {
+            "allow" => Some(LintLevel::Allow),
+            "warn" => Some(LintLevel::Warn),
+            "deny" => Some(LintLevel::Deny),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Lint {
+    pub name: &'static str,
+    pub default_level: LintLevel,
+    pub description: &'static str,
+}
+
+pub const UNUSED_VARIABLE: Lint = Lint {
+    name: "unused_variable",
+    default_level: LintLevel::Warn,
+    description: "local variables that are declared but never used",
+};
+pub const UNKNOWN_LINTS: Lint = Lint {
+    name: "unknown_lints",
+    default_level: LintLevel::Warn,
+    description: "lint attributes naming a lint that doesn't exist",
+};
+
+pub const INVALID_ATTRIBUTES: Lint = Lint {
+    name: "invalid_attributes",
+    default_level: LintLevel::Warn,
+    description:
+        "attributes of the language on the wrong kind of statement or with the wrong arguments",
+};
+
+pub const LINTS: &[&Lint] = &[&UNUSED_VARIABLE, &UNKNOWN_LINTS, &INVALID_ATTRIBUTES];
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum AttributeTarget {
+    Function,
+    /// Any function, class, interface, enum, enum variant or variable
+    Declaration,
+}
+
+/// Attributes with a meaning in the language, with what they can be put on and how
+/// many string arguments they take. Other attributes are left to backends and tools.
+const KNOWN_ATTRIBUTES: &[(&str, AttributeTarget, RangeInclusive<usize>, &str)] = &[
+    ("export", AttributeTarget::Declaration, 0..=0, "@export"),
+    ("inline", AttributeTarget::Function, 0..=0, "@inline"),
+    ("test", AttributeTarget::Function, 0..=0, "@test"),
+    (
+        "wasm_import",
+        AttributeTarget::Function,
+        2..=2,
+        "@wasm_import(\"module\", \"name\")",
+    ),
+    (
+        "deprecated",
+        AttributeTarget::Declaration,
+        0..=1,
+        "@deprecated(\"reason\")",
+    ),
+];
+
+pub fn find_lint(name: &str) -> Option<&'static Lint> {
+    LINTS.iter().find(|lint| lint.name == name).copied()
+}
+
+/// Lint levels set on the command line, on top of each lint's default level.
+#[derive(Debug, Default, Clone)]
+pub struct LintLevels {
+    levels: HashMap<&'static str, LintLevel>,
+}
+impl LintLevels {
+    /// Parses `-W name`, `-D name` and `-A name` (or `-Wname`) flags, ignoring
+    /// every other argument.
+    pub fn from_args<I, S>(args: I) -> Result<LintLevels, String>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut levels = LintLevels::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let arg = arg.as_ref();
+            let level = match arg.get(..2) {
+                Some("-W") => LintLevel::Warn,
+                Some("-D") => LintLevel::Deny,
+                Some("-A") => LintLevel::Allow,
+                _ => continue,
+            };
+            let name = match &arg[2..] {
+                "" => match args.next() {
+                    Some(name) => name.as_ref().to_string(),
+                    None => return Err(format!("Expected a lint name after `{}`", arg)),
+                },
+                name => name.to_string(),
+            };
+            levels.set(&name, level)?;
+        }
+        Ok(levels)
+    }
+    pub fn set(&mut self, name: &str, level: LintLevel) -> Result<(), String> {
+        match find_lint(name) {
+            Some(lint) => {
+                self.levels.insert(lint.name, level);
+                Ok(())
+            }
+            None => Err(format!("Unknown lint `{}`", name)),
+        }
+    }
+    pub fn level(&self, lint: &Lint) -> LintLevel {
+        self.levels
+            .get(lint.name)
+            .copied()
+            .unwrap_or(lint.default_level)
+    }
+}
+
+/// Runs every lint over a parsed file.
+pub fn check(statements: &[Statement], levels: &LintLevels) -> Vec<Diagnostic> {
+    let mut cx = LintContext {
+        levels: vec![levels.clone()],
+        scopes: Vec::new(),
+        diagnostics: Vec::new(),
+    };
+    for statement in statements {
+        cx.statement(statement);
+    }
+    cx.diagnostics
+}
+
+struct Variable {
+    name: String,
+    span: Range<usize>,
+    used: bool,
+    /// The lint levels in effect where the variable was declared.
+    levels: LintLevels,
+}
+
+struct LintContext {
+    /// Lint levels, with the innermost attributes last.
+    levels: Vec<LintLevels>,
+    /// Local variables of the enclosing blocks, with the innermost block last.
+    scopes: Vec<Vec<Variable>>,
+    diagnostics: Vec<Diagnostic>,
+}
+impl LintContext {
+    fn emit(&mut self, levels: &LintLevels, lint: &Lint, diagnostic: Diagnostic) {
+        let level = match levels.level(lint) {
+            LintLevel::Allow => return,
+            LintLevel::Warn => Level::Warning,
+            LintLevel::Deny => Level::Error,
+        };
+        self.diagnostics.push(Diagnostic {
+            level,
+            lint: Some(lint.name),
+            ..diagnostic
+        });
+    }
+    fn push_attributes(&mut self, attributes: &[Attribute]) {
+        let mut levels = self.levels.last().unwrap().clone();
+        for attribute in attributes {
+            let Some(level) = LintLevel::from_attribute(&attribute.name) else {
+                continue;
+            };
+            for arg in &attribute.args {
+                let name = match arg {
+                    Expression::Identifier(name) => name.as_str(),
+                    _ => {
+                        let diagnostic =
+                            Diagnostic::error(attribute.span.clone(), "Expected a lint name");
+                        self.emit(&levels, &UNKNOWN_LINTS, diagnostic);
+                        continue;
+                    }
+                };
+                if levels.set(name, level).is_ok() {
+                    continue;
+                }
+                let mut diagnostic =
+                    Diagnostic::error(attribute.span.clone(), format!("Unknown lint `{}`", name));
+                if let Some(lint) = closest_match(name, LINTS.iter().map(|lint| lint.name)) {
+                    diagnostic = diagnostic.with_suggestion(
+                        format!("did you mean `{}`?", lint),
+                        attribute.span.clone(),
+                        format!("@{}({})", attribute.name, lint),
+                        Applicability::MaybeIncorrect,
+                    );
+                }
+                self.emit(&levels, &UNKNOWN_LINTS, diagnostic);
+            }
+        }
+        self.levels.push(levels);
+    }
+    /// Checks the attributes of the language in front of `target`, which is `None` for
+    /// enum variants.
+    fn check_attributes(&mut self, attributes: &[Attribute], target: Option<&Statement>) {
+        let levels = self.levels.last().unwrap().clone();
+        let target = match target.map(Statement::inner) {
+            Some(Statement::FunctionDecl { .. }) => Some(AttributeTarget::Function),
+            Some(
+                Statement::ClassDecl { .. }
+                | Statement::InterfaceDecl { .. }
+                | Statement::EnumDecl { .. }
+                | Statement::VariableDecl { .. },
+            )
+            | None => Some(AttributeTarget::Declaration),
+            Some(_) => None,
+        };
+        for attribute in attributes {
+            let Some((name, allowed, arity, usage)) = KNOWN_ATTRIBUTES
+                .iter()
+                .find(|(name, ..)| *name == attribute.name)
+            else {
+                continue;
+            };
+            let message = match (allowed, target) {
+                (AttributeTarget::Function, Some(AttributeTarget::Function))
+                | (AttributeTarget::Declaration, Some(_)) => match attribute.string_args() {
+                    Some(args) if arity.contains(&args.len()) => continue,
+                    _ if *arity.end() == 0 => format!("`@{}` takes no arguments", name),
+                    _ => format!("Expected `{}`", usage),
+                },
+                (AttributeTarget::Function, _) => {
+                    format!("`@{}` can only be used on functions", name)
+                }
+                (AttributeTarget::Declaration, None) => {
+                    format!("`@{}` can only be used on declarations", name)
+                }
+            };
+            let diagnostic = Diagnostic::error(attribute.span.clone(), message);
+            self.emit(&levels, &INVALID_ATTRIBUTES, diagnostic);
+        }
+    }
+    /// Pushes a scope with names bound by a loop or pattern. They shadow outer
+    /// variables, but aren't linted themselves.
+    fn push_bindings<'a>(&mut self, names: impl IntoIterator<Item = &'a str>) {
+        let scope = names
+            .into_iter()
+            .map(|name| Variable {
+                name: name.to_string(),
+                span: 0..0,
+                used: true,
+                levels: LintLevels::default(),
+            })
+            .collect();
+        self.scopes.push(scope);
+    }
+    fn use_variable(&mut self, name: &str) {
+        for scope in self.scopes.iter_mut().rev() {
+            if let Some(variable) = scope.iter_mut().rev().find(|v| v.name == name) {
+                variable.used = true;
+                return;
+            }
+        }
+    }
+    fn statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Annotated(attributes, statement) => {
+                self.push_attributes(attributes);
+                self.check_attributes(attributes, Some(statement));
+                self.statement(statement);
+                self.levels.pop();
+            }
+            Statement::Modified(_, statement) => self.statement(statement),
+            Statement::ConstructorDecl(params, body)
+            | Statement::FunctionDecl {
+                params,
+                body: Some(body),
+                ..
+            } => {
+                self.push_bindings(params.iter().flat_map(|(p, _)| p.bindings()));
+                self.expression(body);
+                self.scopes.pop();
+            }
+            Statement::FunctionDecl { body: None, .. } => {}
+            Statement::ClassDecl { members, .. } | Statement::InterfaceDecl { members, .. } => {
+                for member in members {
+                    self.statement(member);
+                }
+            }
+            Statement::EnumDecl { variants, .. } => {
+                for variant in variants {
+                    self.push_attributes(&variant.attributes);
+                    self.check_attributes(&variant.attributes, None);
+                    self.levels.pop();
+                }
+            }
+            Statement::Import(_) => {}
+            Statement::Return(value) => {
+                if let Some(value) = value {
+                    self.expression(value);
+                }
+            }
+            Statement::VariableDecl {
+                pattern,
+                value,
+                span,
+                ..
+            } => {
+                if let Some(value) = value {
+                    self.expression(value);
+                }
+                let levels = self.levels.last().unwrap().clone();
+                if let Some(scope) = self.scopes.last_mut() {
+                    for name in pattern.bindings() {
+                        scope.push(Variable {
+                            name: name.to_string(),
+                            span: span.clone(),
+                            used: false,
+                            levels: levels.clone(),
+                        });
+                    }
+                }
+            }
+            Statement::Assign(left, _, right, _) => {
+                // Assigning to a variable is a write, not a use, but member
+                // and index targets still read the object they change.
+                if !matches!(left, Expression::Identifier(_)) {
+                    self.expression(left);
+                }
+                self.expression(right);
+            }
+            Statement::Expression(expr) => self.expression(expr),
+        }
+    }
+    fn expression(&mut self, expr: &Expression) {
+        match expr {
+            Expression::Binary(left, _, right)
+            | Expression::Coalesce(left, right)
+            | Expression::Range {
+                start: left,
+                end: right,
+                ..
+            } => {
+                self.expression(left);
+                self.expression(right);
+            }
+            Expression::Conditional(cond, then, otherwise) => {
+                self.expression(cond);
+                self.expression(then);
+                self.expression(otherwise);
+            }
+            Expression::Unary(_, expr) => self.expression(expr),
+            Expression::Block(statements, value) => {
+                self.scopes.push(Vec::new());
+                for statement in statements {
+                    self.statement(statement);
+                }
+                if let Some(value) = value {
+                    self.expression(value);
+                }
+                for variable in self.scopes.pop().unwrap() {
+                    if !variable.used && !variable.name.starts_with('_') {
+                        let diagnostic = Diagnostic::error(
+                            variable.span,
+                            format!("Unused variable `{}`", variable.name),
+                        );
+                        self.emit(&variable.levels, &UNUSED_VARIABLE, diagnostic);
+                    }
+                }
+            }
+            Expression::If(cond, then, otherwise) => {
+                self.expression(cond);
+                self.expression(then);
+                if let Some(otherwise) = otherwise {
+                    self.expression(otherwise);
+                }
+            }
+            Expression::While(_, cond, body) => {
+                self.expression(cond);
+                self.expression(body);
+            }
+            Expression::Loop(_, body) => self.expression(body),
+            Expression::For(_, item, iterable, body) => {
+                self.expression(iterable);
+                self.push_bindings(item.bindings());
+                self.expression(body);
+                self.scopes.pop();
+            }
+            Expression::Match(value, arms) => {
+                self.expression(value);
+                for arm in arms {
+                    self.push_bindings(arm.pattern.bindings());
+                    if let Some(guard) = &arm.guard {
+                        self.expression(guard);
+                    }
+                    self.expression(&arm.body);
+                    self.scopes.pop();
+                }
+            }
+            Expression::Break(_, value) => {
+                if let Some(value) = value {
+                    self.expression(value);
+                }
+            }
+            Expression::Continue(_) => {}
+            Expression::Try {
+                body,
+                catch,
+                finally,
+            } => {
+                self.expression(body);
+                if let Some(catch) = catch {
+                    self.push_bindings(catch.pattern.iter().flat_map(Pattern::bindings));
+                    self.expression(&catch.body);
+                    self.scopes.pop();
+                }
+                if let Some(finally) = finally {
+                    self.expression(finally);
+                }
+            }
+            Expression::Throw(error) => self.expression(error),
+            Expression::Lambda { params, body, .. } => {
+                self.push_bindings(params.iter().flat_map(|(p, _)| p.bindings()));
+                self.expression(body);
+                self.scopes.pop();
+            }
+            Expression::Array(items) => {
+                for item in items {
+                    self.expression(item);
+                }
+            }
+            Expression::Map(entries) => {
+                for (_, value) in entries {
+                    self.expression(value);
+                }
+            }
+            Expression::Spread(expr) => self.expression(expr),
+            Expression::Call { callee, args, .. } => {
+                self.expression(callee);
+                for arg in args {
+                    self.expression(arg);
+                }
+            }
+            Expression::Member { object, .. } => self.expression(object),
+            Expression::Index { object, index } => {
+                self.expression(object);
+                self.expression(index);
+            }
+            Expression::Identifier(name) => self.use_variable(name),
+            Expression::Super | Expression::SelfValue => {}
+            Expression::New { args, .. } => {
+                for arg in args {
+                    self.expression(arg);
+                }
+            }
+            Expression::StringLiteral(_)
+            | Expression::SignedLiteral(_)
+            | Expression::UnsignedLiteral(_)
+            | Expression::FloatLiteral(_)
+            | Expression::BoolLiteral(_)
+            | Expression::CharLiteral(_)
+            | Expression::NullLiteral => {}
+        }
+    }
+}
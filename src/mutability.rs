@@ -0,0 +1,551 @@
+use crate::error::{Applicability, Diagnostic};
+use crate::parser::{Expression, Mutability, Pattern, Statement, Type};
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// Checks that only mutable variables and fields are assigned to, and that the
+/// values of constants are known at compile time.
+///
+/// Immutable variables declared without a value can be assigned once, but not in a
+/// loop or function that may run more than once. The same goes for immutable fields
+/// declared without a value in constructors. Fields are only known for classes declared
+/// at the top level of `statements`.
+pub fn check_assignments(statements: &[Statement]) -> Vec<Diagnostic> {
+    let mut classes = HashMap::new();
+    for statement in statements {
+        if let Statement::ClassDecl {
+            name,
+            extends,
+            members,
+            ..
+        } = statement.inner()
+        {
+            let extends = match extends {
+                Some(Type::Named(path, _)) if path.len() == 1 => Some(path[0].as_str()),
+                _ => None,
+            };
+            classes.insert(name.as_str(), Class { extends, members });
+        }
+    }
+    let mut cx = Checker {
+        classes,
+        scopes: vec![Vec::new()],
+        class: None,
+        in_constructor: false,
+        repeated: 0,
+        diagnostics: Vec::new(),
+    };
+    // Functions can assign to variables declared further down the file
+    for statement in statements {
+        if let Statement::VariableDecl {
+            mutability,
+            pattern,
+            value,
+            span,
+            ..
+        } = statement.inner()
+        {
+            for name in pattern.bindings() {
+                cx.bind(name, *mutability, Some(span.clone()), value.is_some());
+            }
+        }
+    }
+    for statement in statements {
+        cx.statement(statement);
+    }
+    cx.diagnostics
+}
+
+struct Class<'a> {
+    extends: Option<&'a str>,
+    members: &'a [Statement],
+}
+
+struct Binding {
+    name: String,
+    mutability: Mutability,
+    /// The span of the name of a variable, which is `None` for parameters and names
+    /// bound by `for` or patterns.
+    span: Option<Range<usize>>,
+    assigned: bool,
+}
+
+struct Checker<'a> {
+    classes: HashMap<&'a str, Class<'a>>,
+    /// Names bound by the enclosing blocks, with the innermost block last.
+    scopes: Vec<Vec<Binding>>,
+    /// The class whose members are being checked.
+    class: Option<&'a str>,
+    in_constructor: bool,
+    /// How many of `scopes` are outside of the innermost loop or function body, which
+    /// may run more than once.
+    repeated: usize,
+    diagnostics: Vec<Diagnostic>,
+}
+impl<'a> Checker<'a> {
+    fn bind(
+        &mut self,
+        name: &str,
+        mutability: Mutability,
+        span: Option<Range<usize>>,
+        assigned: bool,
+    ) {
+        self.scopes.last_mut().unwrap().push(Binding {
+            name: name.to_string(),
+            mutability,
+            span,
+            assigned,
+        });
+    }
+    /// Runs `f` in a new scope where `names` are bound immutably.
+    fn scoped<'n>(&mut self, names: impl IntoIterator<Item = &'n str>, f: impl FnOnce(&mut Self)) {
+        self.scopes.push(Vec::new());
+        for name in names {
+            self.bind(name, Mutability::Immutable, None, true);
+        }
+        f(self);
+        self.scopes.pop();
+    }
+    /// Runs `f` on the body of a loop or function, which may run more than once.
+    fn repeated(&mut self, f: impl FnOnce(&mut Self)) {
+        let outer = std::mem::replace(&mut self.repeated, self.scopes.len());
+        f(self);
+        self.repeated = outer;
+    }
+    /// Whether `name` is bound outside of the innermost loop or function body.
+    fn is_outside_repeated(&self, name: &str) -> bool {
+        self.scopes
+            .iter()
+            .rposition(|scope| scope.iter().any(|b| b.name == name))
+            .is_some_and(|i| i < self.repeated)
+    }
+    fn lookup(&mut self, name: &str) -> Option<&mut Binding> {
+        self.scopes
+            .iter_mut()
+            .rev()
+            .flat_map(|scope| scope.iter_mut().rev())
+            .find(|b| b.name == name)
+    }
+    /// Which variables have been assigned, to be restored with [`Checker::restore`].
+    fn snapshot(&self) -> Vec<bool> {
+        self.scopes.iter().flatten().map(|b| b.assigned).collect()
+    }
+    fn restore(&mut self, assigned: &[bool]) {
+        for (binding, assigned) in self.scopes.iter_mut().flatten().zip(assigned) {
+            binding.assigned = *assigned;
+        }
+    }
+    /// Treats variables assigned in `assigned` as assigned too, after checking
+    /// branches of which only one runs.
+    fn merge(&mut self, assigned: &[bool]) {
+        for (binding, assigned) in self.scopes.iter_mut().flatten().zip(assigned) {
+            binding.assigned |= *assigned;
+        }
+    }
+    /// The field `name` of `class` or one of its superclasses, with its mutability and
+    /// the span of its name.
+    fn field(
+        &self,
+        class: &str,
+        name: &str,
+        is_static: bool,
+    ) -> Option<(Mutability, Range<usize>)> {
+        let mut class = self.classes.get(class);
+        // A class can't extend itself, so it's at most as deep as there are classes
+        for _ in 0..self.classes.len() {
+            let current = class?;
+            for member in current.members {
+                let member_is_static = member.modifiers().is_some_and(|m| m.is_static);
+                match member.inner() {
+                    Statement::VariableDecl {
+                        mutability, span, ..
+                    } if member.name() == Some(name) && member_is_static == is_static => {
+                        return Some((*mutability, span.clone()));
+                    }
+                    _ => {}
+                }
+            }
+            class = current.extends.and_then(|name| self.classes.get(name));
+        }
+        None
+    }
+    /// The instance fields of `class` and its superclasses, with their mutability, the
+    /// spans of their names and whether they're declared with a value.
+    fn instance_fields(&self, class: &str) -> Vec<(&'a str, Mutability, Range<usize>, bool)> {
+        let mut fields: Vec<(&str, _, _, _)> = Vec::new();
+        let mut class = self.classes.get(class);
+        for _ in 0..self.classes.len() {
+            let Some(current) = class else {
+                break;
+            };
+            for member in current.members {
+                if member.modifiers().is_some_and(|m| m.is_static) {
+                    continue;
+                }
+                if let (
+                    Statement::VariableDecl {
+                        mutability,
+                        span,
+                        value,
+                        ..
+                    },
+                    Some(name),
+                ) = (member.inner(), member.name())
+                {
+                    // Fields of subclasses shadow those of their superclasses
+                    if !fields.iter().any(|(field, ..)| *field == name) {
+                        fields.push((name, *mutability, span.clone(), value.is_some()));
+                    }
+                }
+            }
+            class = current.extends.and_then(|name| self.classes.get(name));
+        }
+        fields
+    }
+    /// Marks the variable or constructor field bound as `name` as assigned, if it's
+    /// declared without a value and this is its first assignment.
+    fn assign_once(&mut self, name: &str) -> bool {
+        let outside_repeated = self.is_outside_repeated(name);
+        match self.lookup(name) {
+            Some(binding) if binding.span.is_some() && !binding.assigned && !outside_repeated => {
+                binding.assigned = true;
+                true
+            }
+            _ => false,
+        }
+    }
+    fn assign(&mut self, target: &Expression, span: &Range<usize>) {
+        let (name, mutability, decl_span, what, twice) = match target {
+            Expression::Identifier(name) => {
+                // Variables declared without a value get it from their first assignment,
+                // unless that may run more than once
+                if self.assign_once(name) {
+                    return;
+                }
+                let Some(binding) = self.lookup(name) else {
+                    return;
+                };
+                let what = match binding.span {
+                    None => "binding",
+                    Some(_) => "variable",
+                };
+                let twice = binding.span.is_some();
+                (name, binding.mutability, binding.span.clone(), what, twice)
+            }
+            Expression::Member { object, field, .. } => {
+                let field_of = match &**object {
+                    Expression::SelfValue => self.class.map(|class| (class, false)),
+                    Expression::Identifier(class) if self.lookup(class).is_none() => self
+                        .classes
+                        .get_key_value(class.as_str())
+                        .map(|(c, _)| (*c, true)),
+                    _ => None,
+                };
+                let Some((class, is_static)) = field_of else {
+                    return;
+                };
+                let Some((mutability, decl_span)) = self.field(class, field, is_static) else {
+                    return;
+                };
+                // Constructors initialize the fields of their instance like variables
+                // declared without a value
+                let key = format!("self.{}", field);
+                let in_constructor = self.in_constructor && !is_static;
+                if in_constructor && self.assign_once(&key) {
+                    return;
+                }
+                let twice = in_constructor && self.lookup(&key).is_some();
+                (field, mutability, Some(decl_span), "field", twice)
+            }
+            _ => return,
+        };
+        let mut diagnostic = match mutability {
+            Mutability::Mutable => return,
+            Mutability::Const => Diagnostic::error(
+                span.clone(),
+                format!("Cannot assign to constant `{}`", name),
+            ),
+            Mutability::Immutable if twice => Diagnostic::error(
+                span.clone(),
+                format!("Cannot assign twice to immutable {} `{}`", what, name),
+            ),
+            Mutability::Immutable => Diagnostic::error(
+                span.clone(),
+                format!("Cannot assign to immutable {} `{}`", what, name),
+            ),
+        };
+        if let (Mutability::Immutable, Some(decl_span)) = (mutability, decl_span) {
+            diagnostic = diagnostic.with_suggestion(
+                "make it mutable",
+                decl_span.start..decl_span.start,
+                "mut ",
+                Applicability::MachineApplicable,
+            );
+        }
+        self.diagnostics.push(diagnostic);
+    }
+    /// Whether `expr` can be computed at compile time, assuming that names from
+    /// outside of the file are constants.
+    fn is_constant(&mut self, expr: &Expression) -> bool {
+        match expr {
+            Expression::Unary(_, expr) | Expression::Spread(expr) => self.is_constant(expr),
+            Expression::Binary(left, _, right)
+            | Expression::Coalesce(left, right)
+            | Expression::Range {
+                start: left,
+                end: right,
+                ..
+            } => self.is_constant(left) && self.is_constant(right),
+            Expression::Conditional(cond, then, otherwise) => {
+                self.is_constant(cond) && self.is_constant(then) && self.is_constant(otherwise)
+            }
+            Expression::Array(items) => items.iter().all(|item| self.is_constant(item)),
+            Expression::Map(entries) => entries.iter().all(|(_, value)| self.is_constant(value)),
+            Expression::Identifier(name) => self
+                .lookup(name)
+                .is_none_or(|b| b.mutability == Mutability::Const),
+            // Paths like `Color.Red`
+            Expression::Member { object, .. } => object.is_path() && self.is_constant(object),
+            Expression::StringLiteral(_)
+            | Expression::SignedLiteral(_)
+            | Expression::UnsignedLiteral(_)
+            | Expression::FloatLiteral(_)
+            | Expression::BoolLiteral(_)
+            | Expression::CharLiteral(_)
+            | Expression::NullLiteral => true,
+            _ => false,
+        }
+    }
+    fn variable_decl(&mut self, statement: &'a Statement) {
+        let Statement::VariableDecl {
+            mutability,
+            value,
+            span,
+            ..
+        } = statement
+        else {
+            return;
+        };
+        if let Some(value) = value {
+            self.expression(value);
+            if *mutability == Mutability::Const && !self.is_constant(value) {
+                self.diagnostics.push(Diagnostic::error(
+                    span.clone(),
+                    "Constants must be known at compile time",
+                ));
+            }
+        }
+    }
+    fn statement(&mut self, statement: &'a Statement) {
+        match statement {
+            Statement::Annotated(_, statement) | Statement::Modified(_, statement) => {
+                self.statement(statement)
+            }
+            Statement::ConstructorDecl(params, body) => {
+                let in_constructor = std::mem::replace(&mut self.in_constructor, true);
+                let fields = self
+                    .class
+                    .map_or_else(Vec::new, |class| self.instance_fields(class));
+                self.repeated(|cx| {
+                    cx.scoped(params.iter().flat_map(|(p, _)| p.bindings()), |cx| {
+                        // Bound under a name no variable can have, so that fields are
+                        // tracked like variables declared without a value
+                        for (name, mutability, span, assigned) in fields {
+                            if mutability == Mutability::Immutable {
+                                let name = format!("self.{}", name);
+                                cx.bind(&name, mutability, Some(span), assigned);
+                            }
+                        }
+                        cx.expression(body)
+                    })
+                });
+                self.in_constructor = in_constructor;
+            }
+            Statement::FunctionDecl { params, body, .. } => {
+                if let Some(body) = body {
+                    let in_constructor = std::mem::replace(&mut self.in_constructor, false);
+                    self.repeated(|cx| {
+                        cx.scoped(params.iter().flat_map(|(p, _)| p.bindings()), |cx| {
+                            cx.expression(body)
+                        })
+                    });
+                    self.in_constructor = in_constructor;
+                }
+            }
+            Statement::ClassDecl { name, members, .. }
+            | Statement::InterfaceDecl { name, members, .. } => {
+                let class = self.class.replace(name);
+                self.scoped([], |cx| {
+                    for member in members {
+                        // Fields aren't variables of the methods
+                        match member.inner() {
+                            field @ Statement::VariableDecl { .. } => cx.variable_decl(field),
+                            _ => cx.statement(member),
+                        }
+                    }
+                });
+                self.class = class;
+            }
+            Statement::EnumDecl { .. } | Statement::Import(_) => {}
+            Statement::Return(value) => {
+                if let Some(value) = value {
+                    self.expression(value);
+                }
+            }
+            Statement::VariableDecl {
+                mutability,
+                pattern,
+                value,
+                span,
+                ..
+            } => {
+                self.variable_decl(statement);
+                // Top-level variables were bound before checking the file
+                if self.scopes.len() > 1 {
+                    for name in pattern.bindings() {
+                        self.bind(name, *mutability, Some(span.clone()), value.is_some());
+                    }
+                }
+            }
+            Statement::Assign(left, _, right, span) => {
+                self.expression(right);
+                self.expression(left);
+                self.assign(left, span);
+            }
+            Statement::Expression(expr) => self.expression(expr),
+        }
+    }
+    fn expression(&mut self, expr: &'a Expression) {
+        match expr {
+            Expression::Binary(left, _, right)
+            | Expression::Coalesce(left, right)
+            | Expression::Range {
+                start: left,
+                end: right,
+                ..
+            } => {
+                self.expression(left);
+                self.expression(right);
+            }
+            // Like an `if`, only one of the branches runs
+            Expression::Conditional(cond, then, otherwise) => {
+                self.expression(cond);
+                let before = self.snapshot();
+                self.expression(then);
+                let after_then = self.snapshot();
+                self.restore(&before);
+                self.expression(otherwise);
+                self.merge(&after_then);
+            }
+            Expression::Unary(_, expr) | Expression::Spread(expr) => self.expression(expr),
+            Expression::Block(statements, value) => self.scoped([], |cx| {
+                for statement in statements {
+                    cx.statement(statement);
+                }
+                if let Some(value) = value {
+                    cx.expression(value);
+                }
+            }),
+            Expression::If(cond, then, otherwise) => {
+                self.expression(cond);
+                let before = self.snapshot();
+                self.expression(then);
+                let after_then = self.snapshot();
+                self.restore(&before);
+                if let Some(otherwise) = otherwise {
+                    self.expression(otherwise);
+                }
+                self.merge(&after_then);
+            }
+            Expression::While(_, cond, body) => self.repeated(|cx| {
+                cx.expression(cond);
+                cx.expression(body);
+            }),
+            Expression::Loop(_, body) => self.repeated(|cx| cx.expression(body)),
+            Expression::For(_, item, iterable, body) => {
+                self.expression(iterable);
+                self.repeated(|cx| cx.scoped(item.bindings(), |cx| cx.expression(body)));
+            }
+            Expression::Match(value, arms) => {
+                self.expression(value);
+                let before = self.snapshot();
+                let mut after = before.clone();
+                for arm in arms {
+                    self.restore(&before);
+                    self.scoped(arm.pattern.bindings(), |cx| {
+                        if let Some(guard) = &arm.guard {
+                            cx.expression(guard);
+                        }
+                        cx.expression(&arm.body);
+                    });
+                    self.merge(&after);
+                    after = self.snapshot();
+                }
+            }
+            Expression::Break(_, value) => {
+                if let Some(value) = value {
+                    self.expression(value);
+                }
+            }
+            Expression::Continue(_) => {}
+            // An error can be thrown after any assignment in the try block, so the
+            // catch block is checked as if it ran after all of them
+            Expression::Try {
+                body,
+                catch,
+                finally,
+            } => {
+                self.expression(body);
+                if let Some(catch) = catch {
+                    let bindings = catch.pattern.iter().flat_map(Pattern::bindings);
+                    self.scoped(bindings, |cx| cx.expression(&catch.body));
+                }
+                if let Some(finally) = finally {
+                    self.expression(finally);
+                }
+            }
+            Expression::Throw(error) => self.expression(error),
+            Expression::Lambda { params, body, .. } => self.repeated(|cx| {
+                cx.scoped(params.iter().flat_map(|(p, _)| p.bindings()), |cx| {
+                    cx.expression(body)
+                })
+            }),
+            Expression::Array(items) => {
+                for item in items {
+                    self.expression(item);
+                }
+            }
+            Expression::Map(entries) => {
+                for (_, value) in entries {
+                    self.expression(value);
+                }
+            }
+            Expression::Call { callee, args, .. } => {
+                self.expression(callee);
+                for arg in args {
+                    self.expression(arg);
+                }
+            }
+            Expression::Member { object, .. } => self.expression(object),
+            Expression::Index { object, index } => {
+                self.expression(object);
+                self.expression(index);
+            }
+            Expression::New { args, .. } => {
+                for arg in args {
+                    self.expression(arg);
+                }
+            }
+            Expression::Identifier(_)
+            | Expression::Super
+            | Expression::SelfValue
+            | Expression::StringLiteral(_)
+            | Expression::SignedLiteral(_)
+            | Expression::UnsignedLiteral(_)
+            | Expression::FloatLiteral(_)
+            | Expression::BoolLiteral(_)
+            | Expression::CharLiteral(_)
+            | Expression::NullLiteral => {}
+        }
+    }
+}
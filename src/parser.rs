@@ -1,28 +1,212 @@
+use crate::error::{apply_fixes, closest_match, Applicability, Diagnostic};
 use crate::lexer::token::keyword::Keyword;
 use crate::lexer::token::Token;
-use crate::lexer::LexerInput;
+use crate::lexer::Lexer;
 use std::ops::Range;
 
+mod captures;
+
+/// How deeply statements and expressions may nest before parsing gives up,
+/// so that malicious input can't overflow the stack.
+const MAX_DEPTH: usize = 64;
+/// How many calls, member accesses, indexes and binary operators may be chained, like
+/// `a.b(c).d + e`. They're parsed in a loop, but nest the syntax tree that later passes
+/// walk recursively.
+const MAX_CHAIN: usize = 256;
+
+/// The kinds of declarations that have members, including the file itself.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum DeclKind {
+    Module,
+    Class,
+    AbstractClass,
+    Interface,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum LoopKind {
+    Loop,
+    While,
+    For,
+}
+
 pub struct ParserInput {
     tokens: Vec<(Token, Range<usize>)>,
     pos: usize,
+    depth: usize,
+    /// The most chained operations on any path through the expressions parsed so far, up
+    /// to `MAX_CHAIN`. An operand's operations count toward every chain it ends up in,
+    /// even when the chain only continues after the operand was parsed.
+    chain: usize,
+    /// The loops around the current position, with their labels, innermost last.
+    loops: Vec<(Option<String>, LoopKind)>,
+    /// Whether the current position is inside a function body, where `return` is allowed.
+    in_function: bool,
+    /// How many of `loops` are outside of the innermost `finally` block, if the current
+    /// position is inside one. Jumps can't leave a `finally` block.
+    finally: Option<usize>,
+    /// The declaration whose members are being parsed, if the current position is
+    /// directly inside of one.
+    members: Option<DeclKind>,
+    /// Whether the current position is inside a class that extends another, where
+    /// `super` is allowed.
+    in_subclass: bool,
+    /// Whether the current position is inside an instance method or constructor,
+    /// where `self` is allowed.
+    in_method: bool,
+    /// Whether the declaration being parsed is marked `static`.
+    is_static: bool,
+    /// Whether the declaration being parsed has `@wasm_import`, so the host provides
+    /// the body of its function.
+    is_import: bool,
 }
 impl ParserInput {
     fn next(&mut self) -> Option<(Token, Range<usize>)> {
-        self.pos += 1;
-        self.tokens.get(self.pos - 1).cloned()
+        let token = self.tokens.get(self.pos).cloned();
+        if token.is_some() {
+            self.pos += 1;
+        }
+        token
     }
     fn peek<F>(&mut self, f: F) -> bool
     where
         F: Fn(&Token) -> bool,
     {
-        self.tokens.get(self.pos).map_or(false, |t| f(&t.0))
+        self.tokens.get(self.pos).is_some_and(|t| f(&t.0))
     }
-    fn rewind(&mut self) {
-        self.pos -= 1;
+    /// Like `peek`, but looks `n` tokens past the next one.
+    fn peek_nth<F>(&self, n: usize, f: F) -> bool
+    where
+        F: Fn(&Token) -> bool,
+    {
+        self.tokens.get(self.pos + n).is_some_and(|t| f(&t.0))
     }
     fn eof(&self) -> bool {
-        self.pos + 1 >= self.tokens.len()
+        self.pos >= self.tokens.len()
+    }
+    /// Consumes a `>` closing type arguments, splitting it off of tokens like `>>`
+    /// so `List<List<u8>>` works.
+    fn split_gt(&mut self) -> bool {
+        let Some((token, span)) = self.tokens.get_mut(self.pos) else {
+            return false;
+        };
+        let rest = match token {
+            Token::Gt => {
+                self.pos += 1;
+                return true;
+            }
+            Token::Shr => Token::Gt,
+            Token::Ge => Token::Assign,
+            Token::ShrAssign => Token::Ge,
+            _ => return false,
+        };
+        *token = rest;
+        span.start += 1;
+        true
+    }
+    /// Consumes the next token and returns its span, or the span of the last token at the end of input.
+    fn next_span(&mut self) -> Range<usize> {
+        self.next()
+            .map_or(self.tokens.last().map_or(0..0, |t| t.1.clone()), |t| t.1)
+    }
+    fn prev_is<F>(&self, f: F) -> bool
+    where
+        F: Fn(&Token) -> bool,
+    {
+        self.pos
+            .checked_sub(1)
+            .and_then(|i| self.tokens.get(i))
+            .is_some_and(|t| f(&t.0))
+    }
+    /// The position right after the previously consumed token.
+    fn prev_end(&self) -> usize {
+        self.pos
+            .checked_sub(1)
+            .and_then(|i| self.tokens.get(i))
+            .map_or(0, |t| t.1.end)
+    }
+    /// Runs `f` one nesting level deeper, failing if the input nests too deeply.
+    fn nested<T>(
+        &mut self,
+        f: impl FnOnce(&mut ParserInput) -> Result<T, Diagnostic>,
+    ) -> Result<T, Diagnostic> {
+        if self.depth >= MAX_DEPTH {
+            let span = self.tokens.get(self.pos).map_or(0..0, |t| t.1.clone());
+            error!(span, "Nesting is too deep");
+        }
+        self.depth += 1;
+        let result = f(self);
+        self.depth -= 1;
+        result
+    }
+    /// Runs `f` inside a loop, so `break` and `continue` can refer to it.
+    fn in_loop<T>(
+        &mut self,
+        label: Option<String>,
+        kind: LoopKind,
+        f: impl FnOnce(&mut ParserInput) -> Result<T, Diagnostic>,
+    ) -> Result<T, Diagnostic> {
+        self.loops.push((label, kind));
+        let result = f(self);
+        self.loops.pop();
+        result
+    }
+    /// Finds the loop that a `break` or `continue` with `label` exits.
+    fn find_loop(
+        &self,
+        keyword: &str,
+        label: &Option<String>,
+        span: Range<usize>,
+    ) -> Result<LoopKind, Diagnostic> {
+        if self.loops.is_empty() {
+            error!(span, "`{}` outside of a loop", keyword);
+        }
+        let found = match label {
+            Some(label) => self.loops.iter().rposition(|l| l.0.as_ref() == Some(label)),
+            None => Some(self.loops.len() - 1),
+        };
+        match found {
+            Some(i) if self.finally.is_some_and(|outside| i < outside) => {
+                error!(span, "`{}` can't leave a `finally` block", keyword)
+            }
+            Some(i) => Ok(self.loops[i].1),
+            None => error!(
+                span,
+                "Undeclared label `'{}`",
+                label.as_deref().unwrap_or("")
+            ),
+        }
+    }
+    fn error(&mut self, msg: &str) -> Diagnostic {
+        Diagnostic::error(self.next_span(), msg)
+    }
+    /// The name after a dot, which may be a keyword like in JavaScript, so that
+    /// `promise.catch(f)` calls the method named `catch`.
+    fn member_name(&mut self) -> Result<String, Diagnostic> {
+        match self.next() {
+            Some((Token::Identifier(name), _)) => Ok(name),
+            Some((Token::Keyword(keyword), _)) => Ok(keyword.as_str().to_string()),
+            Some((_, span)) => error!(span, "Expected identifier after dot"),
+            None => error!(self.next_span(), "Expected identifier after dot"),
+        }
+    }
+    /// An error for a missing token that can be fixed by inserting `insert` after the previous token.
+    ///
+    /// Only separators are certain enough for `fix` to insert, as they're only missing
+    /// between two complete items. Any other token is a guess, since the input may just as
+    /// well have an extra token where the missing one was expected.
+    fn missing(&mut self, msg: &str, insert: &str) -> Diagnostic {
+        let end = self.prev_end();
+        let applicability = match insert {
+            ";" | "," => Applicability::MachineApplicable,
+            _ => Applicability::MaybeIncorrect,
+        };
+        self.error(msg).with_suggestion(
+            format!("insert `{}`", insert),
+            end..end,
+            insert,
+            applicability,
+        )
     }
 }
 impl<T> From<T> for ParserInput
@@ -33,674 +217,2437 @@ where
         ParserInput {
             tokens: tokens.collect(),
             pos: 0,
+            depth: 0,
+            chain: 0,
+            loops: Vec::new(),
+            in_function: false,
+            finally: None,
+            members: Some(DeclKind::Module),
+            in_subclass: false,
+            in_method: false,
+            is_static: false,
+            is_import: false,
         }
     }
 }
+impl TryFrom<Lexer> for ParserInput {
+    type Error = Diagnostic;
+    fn try_from(lexer: Lexer) -> Result<Self, Self::Error> {
+        Ok(ParserInput {
+            tokens: lexer.collect::<Result<_, _>>()?,
+            pos: 0,
+            depth: 0,
+            chain: 0,
+            loops: Vec::new(),
+            in_function: false,
+            finally: None,
+            members: Some(DeclKind::Module),
+            in_subclass: false,
+            in_method: false,
+            is_static: false,
+            is_import: false,
+        })
+    }
+}
 impl Clone for ParserInput {
     fn clone(&self) -> Self {
         ParserInput {
             tokens: self.tokens.clone(),
             pos: 0,
+            depth: 0,
+            chain: 0,
+            loops: Vec::new(),
+            in_function: false,
+            finally: None,
+            members: Some(DeclKind::Module),
+            in_subclass: false,
+            in_method: false,
+            is_static: false,
+            is_import: false,
         }
     }
 }
 
+/// Lexes and parses a whole source file.
+pub fn parse(source: &str) -> Result<Vec<Statement>, Diagnostic> {
+    let mut input = ParserInput::try_from(Lexer::new(source))?;
+    let mut statements = Vec::new();
+    while let Some(statement) = Statement::parse(&mut input)? {
+        statements.push(statement);
+    }
+    if !input.eof() {
+        error!(input.next_span(), "Unexpected token");
+    }
+    Ok(statements)
+}
+
+const MAX_FIX_ROUNDS: usize = 100;
+
+/// Repeatedly parses `source` and applies the machine-applicable fixes of the
+/// first error, until it parses or the fixes stop getting parsing any further.
+pub fn fix(source: &str) -> String {
+    let mut source = source.to_string();
+    let Err(mut diagnostic) = parse(&source) else {
+        return source;
+    };
+    for _ in 0..MAX_FIX_ROUNDS {
+        let fixed = apply_fixes(&source, std::slice::from_ref(&diagnostic));
+        if fixed == source {
+            break;
+        }
+        match parse(&fixed) {
+            Ok(_) => return fixed,
+            Err(next) if unparsed(&fixed, &next) < unparsed(&source, &diagnostic) => {
+                source = fixed;
+                diagnostic = next;
+            }
+            Err(_) => break,
+        }
+    }
+    source
+}
+
+/// How many characters of `source` come after the error that stopped parsing it.
+pub fn unparsed(source: &str, diagnostic: &Diagnostic) -> usize {
+    source.chars().count().saturating_sub(diagnostic.span.start)
+}
+
 #[derive(Debug)]
 pub enum Statement {
-    FunctionDecl(
-        String,
-        Option<Expression>,
-        Vec<(String, Expression)>,
-        Expression,
-    ),
-    ClassDecl(String, Vec<Statement>),
-    Import(Vec<Expression>),
-    VariableDecl(String, Option<Expression>, Option<Expression>),
-    Assign(Expression, Token, Expression),
+    /// A function, where the body is missing for abstract methods and method signatures
+    /// of interfaces.
+    FunctionDecl {
+        name: String,
+        type_params: Vec<TypeParam>,
+        params: Vec<(Pattern, Type)>,
+        return_type: Option<Type>,
+        body: Option<Expression>,
+    },
+    /// `constructor(params) { ... }` in a class
+    ConstructorDecl(Vec<(Pattern, Type)>, Expression),
+    /// `abstract class Name<T> extends Base implements A, B { ... }`
+    ClassDecl {
+        name: String,
+        type_params: Vec<TypeParam>,
+        is_abstract: bool,
+        extends: Option<Type>,
+        implements: Vec<Type>,
+        members: Vec<Statement>,
+    },
+    /// `interface Name<T> extends A, B { function method() -> u8; }`
+    InterfaceDecl {
+        name: String,
+        type_params: Vec<TypeParam>,
+        extends: Vec<Type>,
+        members: Vec<Statement>,
+    },
+    /// `enum Name<T> { Circle(radius: f64), Empty }` or `enum Color { Red = 1, Green }`
+    EnumDecl {
+        name: String,
+        type_params: Vec<TypeParam>,
+        variants: Vec<EnumVariant>,
+    },
+    /// `import Foo.Bar as Baz, Foo.{Qux, *}, "./widget.js" as Widget`
+    Import(Vec<Import>),
+    Return(Option<Expression>),
+    /// `let x: u8 = 1`, `let mut (a, b)` or `const X = 1`, with the span of the pattern
+    VariableDecl {
+        mutability: Mutability,
+        pattern: Box<Pattern>,
+        var_type: Option<Type>,
+        value: Option<Expression>,
+        span: Range<usize>,
+    },
+    Annotated(Vec<Attribute>, Box<Statement>),
+    /// A declaration with modifiers like `static`
+    Modified(Modifiers, Box<Statement>),
+    /// `target = value` or `target += value`, with the span of the target
+    Assign(Expression, Token, Expression, Range<usize>),
     Expression(Expression),
 }
 impl Statement {
-    pub fn parse(input: &mut ParserInput, lexer_input: &mut LexerInput) -> Option<Statement> {
+    pub fn parse(input: &mut ParserInput) -> Result<Option<Statement>, Diagnostic> {
         if input.eof() {
-            return None;
+            return Ok(None);
         }
-        Statement::function_decl(input, lexer_input)
+        input.nested(Statement::annotated)
     }
-    pub fn function_decl(
-        input: &mut ParserInput,
-        lexer_input: &mut LexerInput,
-    ) -> Option<Statement> {
-        if input.peek(|t| matches!(t, Token::Keyword(Keyword::Function))) {
+    pub fn annotated(input: &mut ParserInput) -> Result<Option<Statement>, Diagnostic> {
+        let mut attributes = Vec::new();
+        while let Some(attribute) = Attribute::parse(input)? {
+            attributes.push(attribute);
+        }
+        let is_import = attributes.iter().any(|a| a.name == "wasm_import");
+        let outer_is_import = std::mem::replace(&mut input.is_import, is_import);
+        let statement = Statement::modified(input);
+        input.is_import = outer_is_import;
+        if attributes.is_empty() {
+            return statement;
+        }
+        let Some(statement) = statement? else {
+            error!(input.next_span(), "Expected statement after attribute");
+        };
+        Ok(Some(Statement::Annotated(attributes, Box::new(statement))))
+    }
+    pub fn modified(input: &mut ParserInput) -> Result<Option<Statement>, Diagnostic> {
+        let modifiers = Modifiers::parse(input)?;
+        let is_static = modifiers.as_ref().is_some_and(|m| m.is_static);
+        let outer_is_static = std::mem::replace(&mut input.is_static, is_static);
+        let statement = Statement::function_decl(input);
+        input.is_static = outer_is_static;
+        let Some(modifiers) = modifiers else {
+            return statement;
+        };
+        match statement? {
+            Some(statement @ (Statement::FunctionDecl { .. } | Statement::VariableDecl { .. })) => {
+                Ok(Some(Statement::Modified(modifiers, Box::new(statement))))
+            }
+            Some(_) if modifiers.is_static => {
+                error!(modifiers.span, "Only functions and variables can be static")
+            }
+            Some(
+                statement @ (Statement::ClassDecl { .. }
+                | Statement::InterfaceDecl { .. }
+                | Statement::EnumDecl { .. }),
+            ) => Ok(Some(Statement::Modified(modifiers, Box::new(statement)))),
+            Some(Statement::ConstructorDecl(..)) => {
+                error!(modifiers.span, "Constructors can't have a visibility")
+            }
+            Some(_) => error!(modifiers.span, "Only declarations can have a visibility"),
+            None => error!(input.next_span(), "Expected declaration after modifiers"),
+        }
+    }
+    pub fn function_decl(input: &mut ParserInput) -> Result<Option<Statement>, Diagnostic> {
+        let is_abstract = input.peek(|t| matches!(t, Token::Keyword(Keyword::Abstract)))
+            && input.peek_nth(1, |t| matches!(t, Token::Keyword(Keyword::Function)));
+        // `function(x) { ... }` without a name is a lambda expression
+        if is_abstract
+            || input.peek(|t| matches!(t, Token::Keyword(Keyword::Function)))
+                && !input.peek_nth(1, |t| matches!(t, Token::OpenParen))
+        {
+            Statement::function(input, is_abstract).map(Some)
+        } else if input.peek(|t| matches!(t, Token::Keyword(Keyword::Constructor))) {
+            Statement::constructor(input).map(Some)
+        } else {
+            Statement::class_decl(input)
+        }
+    }
+    /// `function name<T>(params) -> T { ... }`, starting at `abstract` or `function`.
+    fn function(input: &mut ParserInput, is_abstract: bool) -> Result<Statement, Diagnostic> {
+        if is_abstract {
+            let span = input.next().unwrap().1;
+            if input.members != Some(DeclKind::AbstractClass) {
+                error!(span, "Only methods of abstract classes can be abstract");
+            }
+        }
+        input.next();
+        let Some(ident) = input.next() else {
+            error!(input.next_span(), "Expected identifier")
+        };
+        let ident_str = match ident.0 {
+            Token::Identifier(s) => s,
+            _ => {
+                error!(ident.1, "Expected identifier");
+            }
+        };
+        let type_params = TypeParam::parse_list(input)?;
+        if !input.peek(|t| matches!(t, Token::OpenParen)) {
+            return Err(input.missing("Expected opening paren", "("));
+        }
+        input.next();
+        let params = Statement::params(input, true)?
+            .into_iter()
+            .map(|(name, param_type)| (name, param_type.unwrap()))
+            .collect();
+        let return_type = if input.peek(|t| matches!(t, Token::Arrow)) {
             input.next();
-            let ident = input.next().unwrap_or_else(|| {
-                error!(
-                    lexer_input.clone(),
-                    input
-                        .next()
-                        .map_or(input.tokens.last().unwrap().1.clone(), |t| t.1),
-                    "Expected identifier"
-                )
-            });
-            let ident_str = match ident.0 {
-                Token::Identifier(s) => s,
-                _ => {
-                    error!(lexer_input.clone(), ident.1, "Expected identifier");
+            Some(Type::parse(input)?)
+        } else {
+            None
+        };
+        // Interface methods without a body are signatures for implementations to fill in,
+        // and imported functions are implemented by the host
+        let in_method = matches!(
+            input.members,
+            Some(DeclKind::Class | DeclKind::AbstractClass | DeclKind::Interface)
+        ) && !input.is_static;
+        let body = if is_abstract
+            || input.is_import
+            || input.members == Some(DeclKind::Interface)
+                && input.peek(|t| matches!(t, Token::Semicolon))
+        {
+            if input.peek(|t| matches!(t, Token::OpenBrace)) {
+                if input.is_import {
+                    error!(input.next_span(), "Imported functions can't have a body");
                 }
-            };
-            if !input.peek(|t| matches!(t, Token::OpenParen)) {
-                error!(
-                    lexer_input.clone(),
-                    input
-                        .next()
-                        .map_or(input.tokens.last().unwrap().1.clone(), |t| t.1),
-                    "Expected opening paren"
-                );
+                error!(input.next_span(), "Abstract methods can't have a body");
+            }
+            if !input.peek(|t| matches!(t, Token::Semicolon)) {
+                return Err(input.missing("Expected semicolon", ";"));
             }
-            let mut params = Vec::new();
             input.next();
-            while input.peek(|t| matches!(t, Token::Identifier(_))) {
-                let name = match input.next().unwrap().0 {
-                    Token::Identifier(s) => s,
-                    _ => unreachable!(),
-                };
-                if !input.peek(|t| matches!(t, Token::Colon)) {
+            None
+        } else {
+            Some(Statement::function_body(input, in_method)?)
+        };
+        Ok(Statement::FunctionDecl {
+            name: ident_str,
+            type_params,
+            params,
+            return_type,
+            body,
+        })
+    }
+    /// `constructor(params) { ... }`, starting at the keyword.
+    fn constructor(input: &mut ParserInput) -> Result<Statement, Diagnostic> {
+        let span = input.next().unwrap().1;
+        if !matches!(
+            input.members,
+            Some(DeclKind::Class | DeclKind::AbstractClass)
+        ) {
+            error!(span, "Constructors are only allowed in classes");
+        }
+        if !input.peek(|t| matches!(t, Token::OpenParen)) {
+            return Err(input.missing("Expected opening paren", "("));
+        }
+        input.next();
+        let params = Statement::params(input, true)?
+            .into_iter()
+            .map(|(name, param_type)| (name, param_type.unwrap()))
+            .collect();
+        let body = Statement::function_body(input, true)?;
+        Ok(Statement::ConstructorDecl(params, body))
+    }
+    /// Parses parameters up to and including the closing paren, like `a: u8, (b, c))`.
+    fn params(
+        input: &mut ParserInput,
+        types_required: bool,
+    ) -> Result<Vec<(Pattern, Option<Type>)>, Diagnostic> {
+        let mut params = Vec::new();
+        while !input.peek(|t| matches!(t, Token::CloseParen)) {
+            let pattern = Pattern::irrefutable(input, "parameters")?;
+            let param_type = if input.peek(|t| matches!(t, Token::Colon)) {
+                input.next();
+                Some(Type::parse(input)?)
+            } else if types_required {
+                return Err(input.missing("Expected colon", ":"));
+            } else {
+                None
+            };
+            params.push((pattern, param_type));
+            if input.peek(|t| matches!(t, Token::CloseParen)) {
+                break;
+            }
+            if !input.peek(|t| matches!(t, Token::Comma)) {
+                return Err(input.missing("Expected comma", ","));
+            } else {
+                input.next();
+            }
+        }
+        if !input.peek(|t| matches!(t, Token::CloseParen)) {
+            return Err(input.missing("Expected closing paren", ")"));
+        }
+        input.next();
+        Ok(params)
+    }
+    /// Parses the body of a function, which has a `self` if `in_method` is set.
+    fn function_body(input: &mut ParserInput, in_method: bool) -> Result<Expression, Diagnostic> {
+        // Loops outside of the function can't be exited from inside of it
+        let loops = std::mem::take(&mut input.loops);
+        let in_function = std::mem::replace(&mut input.in_function, true);
+        let finally = input.finally.take();
+        let outer_in_method = std::mem::replace(&mut input.in_method, in_method);
+        let body = Expression::parse(input);
+        input.loops = loops;
+        input.in_function = in_function;
+        input.finally = finally;
+        input.in_method = outer_in_method;
+        body?.ok_or_else(|| input.error("Expected expression"))
+    }
+    /// Parses the members of a class or interface, from the opening to the closing brace.
+    fn members(
+        input: &mut ParserInput,
+        kind: DeclKind,
+        in_subclass: bool,
+    ) -> Result<Vec<Statement>, Diagnostic> {
+        if !input.peek(|t| matches!(t, Token::OpenBrace)) {
+            error!(input.next_span(), "Expected opening brace");
+        }
+        input.next();
+        let outer_members = input.members.replace(kind);
+        let outer_in_subclass = std::mem::replace(&mut input.in_subclass, in_subclass);
+        let members = Statement::member_list(input, kind);
+        input.members = outer_members;
+        input.in_subclass = outer_in_subclass;
+        let members = members?;
+        if !input.peek(|t| matches!(t, Token::CloseBrace)) {
+            return Err(input.missing("Expected closing brace", "}"));
+        }
+        input.next();
+        Ok(members)
+    }
+    fn member_list(input: &mut ParserInput, kind: DeclKind) -> Result<Vec<Statement>, Diagnostic> {
+        let mut members = Vec::new();
+        let mut has_constructor = false;
+        while let Some(member) = Statement::parse(input)? {
+            match (kind, member.inner()) {
+                (_, Statement::ConstructorDecl(..)) if has_constructor => {
                     error!(
-                        lexer_input.clone(),
-                        input
-                            .next()
-                            .map_or(input.tokens.last().unwrap().1.clone(), |t| t.1),
-                        "Expected colon"
+                        input.tokens[input.pos - 1].1.clone(),
+                        "A class can only have one constructor"
                     );
                 }
-                input.next();
-                let param_type = Expression::parse(input, lexer_input).unwrap_or_else(|| {
+                (_, Statement::ConstructorDecl(..)) => {
+                    has_constructor = true;
+                    members.push(member);
+                }
+                (DeclKind::Interface, Statement::FunctionDecl { .. }) => members.push(member),
+                (DeclKind::Interface, _) => {
                     error!(
-                        lexer_input.clone(),
-                        input
-                            .next()
-                            .map_or(input.tokens.last().unwrap().1.clone(), |t| t.1),
-                        "Expected expression"
+                        input.next_span(),
+                        "Only functions can be members of interfaces"
                     );
-                });
-                params.push((name, param_type));
-                if input.peek(|t| matches!(t, Token::CloseParen)) {
-                    break;
                 }
-                if !input.peek(|t| matches!(t, Token::Comma)) {
+                (_, Statement::VariableDecl { pattern, .. })
+                    if !matches!(**pattern, Pattern::Binding(_)) =>
+                {
                     error!(
-                        lexer_input.clone(),
-                        input
-                            .next()
-                            .map_or(input.tokens.last().unwrap().1.clone(), |t| t.1),
-                        "Expected comma"
+                        input.tokens[input.pos - 1].1.clone(),
+                        "Fields can't be destructured"
                     );
-                } else {
-                    input.next();
                 }
-            }
-            if !input.peek(|t| matches!(t, Token::CloseParen)) {
-                error!(
-                    lexer_input.clone(),
-                    input
-                        .next()
-                        .map_or(input.tokens.last().unwrap().1.clone(), |t| t.1),
-                    "Expected closing paren"
-                );
-            } else {
-                input.next();
-            }
-            let return_type = if input.peek(|t| matches!(t, Token::Arrow)) {
-                input.next();
-                Some(Expression::parse(input, lexer_input).unwrap_or_else(|| {
+                (_, Statement::FunctionDecl { .. } | Statement::VariableDecl { .. }) => {
+                    members.push(member)
+                }
+                _ => {
                     error!(
-                        lexer_input.clone(),
-                        input
-                            .next()
-                            .map_or(input.tokens.last().unwrap().1.clone(), |t| t.1),
-                        "Expected expression"
+                        input.next_span(),
+                        "Only functions and variables can be members of classes"
                     );
-                }))
-            } else {
-                None
-            };
-            let expr = Expression::parse(input, lexer_input).unwrap_or_else(|| {
-                error!(
-                    lexer_input.clone(),
-                    input
-                        .next()
-                        .map_or(input.tokens.last().unwrap().1.clone(), |t| t.1),
-                    "Expected expression"
-                );
-            });
-            Some(Statement::FunctionDecl(
-                ident_str,
-                return_type,
-                params,
-                expr,
-            ))
-        } else {
-            Statement::class_decl(input, lexer_input)
+                }
+            }
         }
+        Ok(members)
     }
-    pub fn class_decl(input: &mut ParserInput, lexer_input: &mut LexerInput) -> Option<Statement> {
-        if input.peek(|t| matches!(t, Token::Keyword(Keyword::Class))) {
+    /// Parses `Name<T>` after `class` or `interface`.
+    fn decl_name(input: &mut ParserInput) -> Result<(String, Vec<TypeParam>), Diagnostic> {
+        let name = match input.next() {
+            Some((Token::Identifier(s), _)) => s,
+            Some((_, span)) => error!(span, "Expected identifier"),
+            None => error!(input.next_span(), "Expected identifier"),
+        };
+        Ok((name, TypeParam::parse_list(input)?))
+    }
+    /// Parses a list of types after a keyword like `implements`, if it's next.
+    fn type_list(input: &mut ParserInput, keyword: Keyword) -> Result<Vec<Type>, Diagnostic> {
+        let mut types = Vec::new();
+        if input.peek(|t| *t == Token::Keyword(keyword.clone())) {
             input.next();
-            let ident = input.next().unwrap_or_else(|| {
-                error!(
-                    lexer_input.clone(),
-                    input
-                        .next()
-                        .map_or(input.tokens.last().unwrap().1.clone(), |t| t.1),
-                    "Expected identifier"
-                )
-            });
-            let ident_str = match ident.0 {
-                Token::Identifier(s) => s,
-                _ => {
-                    error!(lexer_input.clone(), ident.1, "Expected identifier");
+            loop {
+                types.push(Type::parse(input)?);
+                if input.peek(|t| matches!(t, Token::Comma)) {
+                    input.next();
+                } else {
+                    break;
                 }
-            };
-            if !input.peek(|t| matches!(t, Token::OpenBrace)) {
-                error!(
-                    lexer_input.clone(),
-                    input
-                        .next()
-                        .map_or(input.tokens.last().unwrap().1.clone(), |t| t.1),
-                    "Expected opening brace"
-                );
             }
+        }
+        Ok(types)
+    }
+    pub fn class_decl(input: &mut ParserInput) -> Result<Option<Statement>, Diagnostic> {
+        let is_abstract = input.peek(|t| matches!(t, Token::Keyword(Keyword::Abstract)))
+            && input.peek_nth(1, |t| matches!(t, Token::Keyword(Keyword::Class)));
+        if is_abstract || input.peek(|t| matches!(t, Token::Keyword(Keyword::Class))) {
+            Statement::class(input, is_abstract).map(Some)
+        } else {
+            Statement::interface_decl(input)
+        }
+    }
+    /// `class Name<T> extends Base implements Other { ... }`, starting at `abstract` or `class`.
+    fn class(input: &mut ParserInput, is_abstract: bool) -> Result<Statement, Diagnostic> {
+        if is_abstract {
             input.next();
-            let mut members = Vec::new();
-            while let Some(member) = Statement::parse(input, lexer_input) {
-                match member {
-                    Statement::FunctionDecl(_, _, _, _) | Statement::VariableDecl(_, _, _) => {
-                        members.push(member)
-                    }
-                    _ => {
-                        error!(
-                            lexer_input.clone(),
-                            input
-                                .next()
-                                .map_or(input.tokens.last().unwrap().1.clone(), |t| t.1),
-                            "Only functions and variables can be members of classes"
-                        );
-                    }
-                }
-            }
-            if !input.peek(|t| matches!(t, Token::CloseBrace)) {
-                error!(
-                    lexer_input.clone(),
-                    input
-                        .next()
-                        .map_or(input.tokens.last().unwrap().1.clone(), |t| t.1),
-                    "Expected closing brace"
-                );
-            } else {
-                input.next();
-            }
-            Some(Statement::ClassDecl(ident_str, members))
+        }
+        input.next();
+        let (name, type_params) = Statement::decl_name(input)?;
+        let extends = if input.peek(|t| matches!(t, Token::Keyword(Keyword::Extends))) {
+            input.next();
+            Some(Type::parse(input)?)
+        } else {
+            None
+        };
+        let implements = Statement::type_list(input, Keyword::Implements)?;
+        let kind = if is_abstract {
+            DeclKind::AbstractClass
+        } else {
+            DeclKind::Class
+        };
+        let members = Statement::members(input, kind, extends.is_some())?;
+        Ok(Statement::ClassDecl {
+            name,
+            type_params,
+            is_abstract,
+            extends,
+            implements,
+            members,
+        })
+    }
+    pub fn interface_decl(input: &mut ParserInput) -> Result<Option<Statement>, Diagnostic> {
+        if input.peek(|t| matches!(t, Token::Keyword(Keyword::Interface))) {
+            Statement::interface(input).map(Some)
+        } else {
+            Statement::enum_decl(input)
+        }
+    }
+    pub fn enum_decl(input: &mut ParserInput) -> Result<Option<Statement>, Diagnostic> {
+        if input.peek(|t| matches!(t, Token::Keyword(Keyword::Enum))) {
+            Statement::enumeration(input).map(Some)
         } else {
-            let val = Statement::import(input, lexer_input);
-            if input.peek(|t| matches!(t, Token::Semicolon)) {
+            Statement::simple(input)
+        }
+    }
+    /// Statements without a body of declarations, which end in a semicolon: imports,
+    /// variables, returns, assignments and expressions.
+    pub fn simple(input: &mut ParserInput) -> Result<Option<Statement>, Diagnostic> {
+        let val = Statement::import(input)?;
+        match val {
+            None => Ok(None),
+            Some(_) if input.peek(|t| matches!(t, Token::Semicolon)) => {
                 input.next();
-                val
-            } else {
-                error!(
-                    lexer_input.clone(),
-                    input
-                        .next()
-                        .map_or(input.tokens.last().unwrap().1.clone(), |t| t.1),
-                    "Expected semicolon"
-                );
+                Ok(val)
+            }
+            // Like in Rust, `if` and blocks don't need a semicolon when used as statements
+            Some(Statement::Expression(ref expr)) if expr.is_block_like() => Ok(val),
+            // The last expression of a block without a semicolon is the block's value
+            Some(Statement::Expression(_)) if input.peek(|t| matches!(t, Token::CloseBrace)) => {
+                Ok(val)
             }
+            Some(_) => Err(input.missing("Expected semicolon", ";")),
         }
     }
-    pub fn import(input: &mut ParserInput, lexer_input: &mut LexerInput) -> Option<Statement> {
+    /// `interface Name<T> extends Other { ... }`, starting at the keyword.
+    fn interface(input: &mut ParserInput) -> Result<Statement, Diagnostic> {
+        input.next();
+        let (name, type_params) = Statement::decl_name(input)?;
+        let extends = Statement::type_list(input, Keyword::Extends)?;
+        let members = Statement::members(input, DeclKind::Interface, false)?;
+        Ok(Statement::InterfaceDecl {
+            name,
+            type_params,
+            extends,
+            members,
+        })
+    }
+    /// `enum Name<T> { ... }`, starting at the keyword.
+    fn enumeration(input: &mut ParserInput) -> Result<Statement, Diagnostic> {
+        input.next();
+        let (name, type_params) = Statement::decl_name(input)?;
+        let variants = EnumVariant::parse_list(input)?;
+        Ok(Statement::EnumDecl {
+            name,
+            type_params,
+            variants,
+        })
+    }
+    pub fn import(input: &mut ParserInput) -> Result<Option<Statement>, Diagnostic> {
         if input.peek(|t| matches!(t, Token::Keyword(Keyword::Import))) {
             input.next();
-            let mut exprs = Vec::new();
-            let mut had_expr = false;
-            while let Some(expr) = Expression::parse(input, lexer_input) {
-                match expr {
-                    Expression::Path(path) => {
-                        if input.peek(|t| matches!(t, Token::OpenBrace)) {
-                            input.next();
-                            while let Some(path2) = Expression::parse(input, lexer_input) {
-                                match path2 {
-                                    Expression::Path(mut path2) => {
-                                        let mut clone = path.clone();
-                                        clone.append(&mut path2);
-                                        exprs.push(Expression::Path(clone));
-                                    }
-                                    _ => {
-                                        error!(
-                                            lexer_input.clone(),
-                                            input.next().map_or(
-                                                input.tokens.last().unwrap().1.clone(),
-                                                |t| t.1
-                                            ),
-                                            "Expected path"
-                                        );
-                                    }
-                                }
-                                if !input.peek(|t| matches!(t, Token::Comma)) {
-                                    break;
-                                } else {
-                                    input.next();
-                                }
-                            }
-                            if !input.peek(|t| matches!(t, Token::CloseBrace)) {
-                                error!(
-                                    lexer_input.clone(),
-                                    input
-                                        .next()
-                                        .map_or(input.tokens.last().unwrap().1.clone(), |t| t.1),
-                                    "Expected closing brace"
-                                );
-                            } else {
-                                input.next();
-                            }
-                        } else {
-                            exprs.push(Expression::Path(path));
-                        }
-                    }
-                    _ => error!(
-                        lexer_input.clone(),
-                        input
-                            .next()
-                            .map_or(input.tokens.last().unwrap().1.clone(), |t| t.1),
-                        "Expected path"
-                    ),
+            let mut imports = Vec::new();
+            loop {
+                imports.push(Import::parse(input)?);
+                if input.peek(|t| matches!(t, Token::Comma)) {
+                    input.next();
+                } else {
+                    break;
                 }
-                had_expr = true;
-            }
-            if !had_expr {
-                error!(
-                    lexer_input.clone(),
-                    input
-                        .next()
-                        .map_or(input.tokens.last().unwrap().1.clone(), |t| t.1),
-                    "Expected expression"
-                );
             }
-            Some(Statement::Import(exprs))
+            Ok(Some(Statement::Import(imports)))
         } else {
-            Statement::variable_decl(input, lexer_input)
+            Statement::variable_decl(input)
         }
     }
-    pub fn variable_decl(
-        input: &mut ParserInput,
-        lexer_input: &mut LexerInput,
-    ) -> Option<Statement> {
-        if input.peek(|t| matches!(t, Token::Keyword(Keyword::Let))) {
-            input.next();
-            let ident = input.next().unwrap_or_else(|| {
-                error!(
-                    lexer_input.clone(),
-                    input
-                        .next()
-                        .map_or(input.tokens.last().unwrap().1.clone(), |t| t.1),
-                    "Expected identifier"
-                )
-            });
-            let ident_str = match ident.0 {
-                Token::Identifier(s) => s,
-                _ => {
-                    error!(lexer_input.clone(), ident.1, "Expected identifier");
+    pub fn variable_decl(input: &mut ParserInput) -> Result<Option<Statement>, Diagnostic> {
+        if input.peek(|t| matches!(t, Token::Keyword(Keyword::Let | Keyword::Const))) {
+            let mutability = match input.next().unwrap().0 {
+                Token::Keyword(Keyword::Const) => Mutability::Const,
+                _ if input.peek(|t| matches!(t, Token::Keyword(Keyword::Mut))) => {
+                    input.next();
+                    Mutability::Mutable
                 }
+                _ => Mutability::Immutable,
             };
+            let start = input.tokens.get(input.pos).map_or(0, |t| t.1.start);
+            let pattern = Box::new(Pattern::irrefutable(input, "`let`")?);
+            let span = start..input.prev_end();
             let var_type = if input.peek(|t| matches!(t, Token::Colon)) {
                 input.next();
-                Some(Expression::parse(input, lexer_input).unwrap_or_else(|| {
-                    error!(
-                        lexer_input.clone(),
-                        input
-                            .next()
-                            .map_or(input.tokens.last().unwrap().1.clone(), |t| t.1),
-                        "Expected expression"
-                    );
-                }))
+                Some(Type::parse(input)?)
             } else {
                 None
             };
-            if !matches!(var_type, Some(Expression::Path(_)) | None) {
-                error!(
-                    lexer_input.clone(),
-                    input
-                        .next()
-                        .map_or(input.tokens.last().unwrap().1.clone(), |t| t.1),
-                    "Expected path"
-                );
-            }
             if input.peek(Token::is_assign_op) {
                 input.next();
-                let expr = Expression::parse(input, lexer_input)
-                    .unwrap_or_else(|| error!(lexer_input.clone(), ident.1, "Expected expression"));
-                Some(Statement::VariableDecl(ident_str, var_type, Some(expr)))
+                let Some(expr) = Expression::parse(input)? else {
+                    error!(span, "Expected expression")
+                };
+                Ok(Some(Statement::VariableDecl {
+                    mutability,
+                    pattern,
+                    var_type,
+                    value: Some(expr),
+                    span,
+                }))
+            } else if mutability == Mutability::Const {
+                error!(span, "Constants must have a value");
             } else if var_type.is_some() {
-                Some(Statement::VariableDecl(ident_str, var_type, None))
+                Ok(Some(Statement::VariableDecl {
+                    mutability,
+                    pattern,
+                    var_type,
+                    value: None,
+                    span,
+                }))
             } else {
                 error!(
-                    lexer_input.clone(),
-                    ident.1, "Variables must have either an explicit type or an initial value"
+                    span,
+                    "Variables must have either an explicit type or an initial value"
                 );
             }
         } else {
-            Statement::assign(input, lexer_input)
+            Statement::return_statement(input)
         }
     }
-    pub fn assign(input: &mut ParserInput, lexer_input: &mut LexerInput) -> Option<Statement> {
-        let left = Expression::parse(input, lexer_input)?;
-        if input.peek(Token::is_assign_op) {
-            let op = input.next().unwrap().0;
-            let right = Expression::parse(input, lexer_input)?;
-            Some(Statement::Assign(left, op, right))
+    pub fn return_statement(input: &mut ParserInput) -> Result<Option<Statement>, Diagnostic> {
+        if input.peek(|t| matches!(t, Token::Keyword(Keyword::Return))) {
+            let span = input.next().unwrap().1;
+            if !input.in_function {
+                error!(span, "`return` outside of a function");
+            }
+            if input.finally.is_some() {
+                error!(span, "`return` can't leave a `finally` block");
+            }
+            let value = if input.peek(Token::begins_expression) {
+                Some(Expression::parse(input)?.ok_or_else(|| input.error("Expected expression"))?)
+            } else {
+                None
+            };
+            Ok(Some(Statement::Return(value)))
         } else {
-            Some(Statement::Expression(left))
+            Statement::assign(input)
         }
     }
-}
-
-#[derive(Debug)]
-pub enum Expression {
-    Binary(Box<Expression>, Token, Box<Expression>),
-    Unary(Token, Box<Expression>),
-    Block(Vec<Statement>),
-    FunctionCall(Vec<String>, Vec<Expression>),
-    Path(Vec<String>),
-    StringLiteral(String),
-    SignedLiteral(i64),
-    UnsignedLiteral(u64),
-    FloatLiteral(f64),
-}
-impl Expression {
-    pub fn parse(input: &mut ParserInput, lexer_input: &mut LexerInput) -> Option<Expression> {
-        if input.eof() {
-            return None;
+    pub fn assign(input: &mut ParserInput) -> Result<Option<Statement>, Diagnostic> {
+        let start = input.pos;
+        let Some(left) = Expression::parse(input)? else {
+            return Ok(None);
+        };
+        // Two identifiers in a row usually means a misspelled keyword, like `fucntion main()`.
+        // Shorter names are within one edit of too many keywords to guess from.
+        if let Expression::Identifier(name) = &left {
+            if name.chars().count() >= 3 && input.peek(|t| matches!(t, Token::Identifier(_))) {
+                let keywords = Keyword::ALL.iter().map(Keyword::as_str);
+                if let Some(keyword) = closest_match(name, keywords) {
+                    let span = input.tokens[start].1.clone();
+                    return Err(Diagnostic::error(
+                        span.clone(),
+                        format!("Unexpected identifier `{}`", name),
+                    )
+                    .with_suggestion(
+                        format!("did you mean `{}`?", keyword),
+                        span,
+                        keyword,
+                        Applicability::MaybeIncorrect,
+                    ));
+                }
+            }
         }
-        Expression::logic(input, lexer_input)
-    }
-    pub fn logic(input: &mut ParserInput, lexer_input: &mut LexerInput) -> Option<Expression> {
-        let left = Expression::bitwise(input, lexer_input)?;
-        if input.peek(|t| matches!(t, Token::And | Token::Or)) {
+        if input.peek(Token::is_assign_op) {
+            let span = input.tokens[start].1.start..input.prev_end();
+            if left.is_optional_chain() {
+                error!(span, "Can't assign to an optional chain");
+            }
             let op = input.next().unwrap().0;
-            let right = Expression::bitwise(input, lexer_input).unwrap_or_else(|| {
-                error!(
-                    lexer_input.clone(),
-                    input
-                        .next()
-                        .map_or(input.tokens.last().unwrap().1.clone(), |t| t.1),
-                    "Expected expression"
-                );
-            });
-            Some(Expression::Binary(Box::new(left), op, Box::new(right)))
+            let right =
+                Expression::parse(input)?.ok_or_else(|| input.error("Expected expression"))?;
+            Ok(Some(Statement::Assign(left, op, right, span)))
         } else {
-            Some(left)
+            Ok(Some(Statement::Expression(left)))
         }
     }
-    pub fn bitwise(input: &mut ParserInput, lexer_input: &mut LexerInput) -> Option<Expression> {
-        let left = Expression::equality(input, lexer_input)?;
-        if input.peek(|t| matches!(t, Token::BitAnd | Token::BitOr | Token::BitXor)) {
-            let op = input.next().unwrap().0;
-            let right = Expression::equality(input, lexer_input).unwrap_or_else(|| {
-                error!(
-                    lexer_input.clone(),
-                    input
-                        .next()
-                        .map_or(input.tokens.last().unwrap().1.clone(), |t| t.1),
-                    "Expected expression"
-                );
-            });
-            Some(Expression::Binary(Box::new(left), op, Box::new(right)))
-        } else {
-            Some(left)
+    /// The statement without any attributes in front of it.
+    pub fn inner(&self) -> &Statement {
+        match self {
+            Statement::Annotated(_, statement) | Statement::Modified(_, statement) => {
+                statement.inner()
+            }
+            statement => statement,
         }
     }
-    pub fn equality(input: &mut ParserInput, lexer_input: &mut LexerInput) -> Option<Expression> {
-        let left = Expression::comparison(input, lexer_input)?;
-        if input.peek(|t| matches!(t, Token::Eq | Token::Ne)) {
-            let op = input.next().unwrap().0;
-            let right = Expression::comparison(input, lexer_input).unwrap_or_else(|| {
-                error!(
-                    lexer_input.clone(),
-                    input
-                        .next()
-                        .map_or(input.tokens.last().unwrap().1.clone(), |t| t.1),
-                    "Expected expression"
-                );
-            });
-            Some(Expression::Binary(Box::new(left), op, Box::new(right)))
-        } else {
-            Some(left)
+    pub fn attributes(&self) -> &[Attribute] {
+        match self {
+            Statement::Annotated(attributes, _) => attributes,
+            _ => &[],
         }
     }
-    pub fn comparison(input: &mut ParserInput, lexer_input: &mut LexerInput) -> Option<Expression> {
-        let left = Expression::shift(input, lexer_input)?;
-        if input.peek(|t| matches!(t, Token::Lt | Token::Le | Token::Gt | Token::Ge)) {
-            let op = input.next().unwrap().0;
-            let right = Expression::shift(input, lexer_input).unwrap_or_else(|| {
-                error!(
-                    lexer_input.clone(),
-                    input
-                        .next()
-                        .map_or(input.tokens.last().unwrap().1.clone(), |t| t.1),
-                    "Expected expression"
-                );
-            });
-            Some(Expression::Binary(Box::new(left), op, Box::new(right)))
-        } else {
-            Some(left)
-        }
+    /// The attribute named `name` in front of this statement, like `deprecated` for
+    /// `@deprecated("use x")`.
+    pub fn attribute(&self, name: &str) -> Option<&Attribute> {
+        self.attributes().iter().find(|a| a.name == name)
     }
-    pub fn shift(input: &mut ParserInput, lexer_input: &mut LexerInput) -> Option<Expression> {
-        let left = Expression::term(input, lexer_input)?;
-        if input.peek(|t| matches!(t, Token::Shl | Token::Shr)) {
-            let op = input.next().unwrap().0;
-            let right = Expression::term(input, lexer_input).unwrap_or_else(|| {
-                error!(
-                    lexer_input.clone(),
-                    input
-                        .next()
-                        .map_or(input.tokens.last().unwrap().1.clone(), |t| t.1),
-                    "Expected expression"
-                );
-            });
-            Some(Expression::Binary(Box::new(left), op, Box::new(right)))
-        } else {
-            Some(left)
+    pub fn modifiers(&self) -> Option<&Modifiers> {
+        match self {
+            Statement::Annotated(_, statement) => statement.modifiers(),
+            Statement::Modified(modifiers, _) => Some(modifiers),
+            _ => None,
         }
     }
-    pub fn term(input: &mut ParserInput, lexer_input: &mut LexerInput) -> Option<Expression> {
-        let left = Expression::factor(input, lexer_input)?;
-        if input.peek(|t| matches!(t, Token::Add | Token::Sub)) {
-            let op = input.next().unwrap().0;
-            let right = Expression::factor(input, lexer_input).unwrap_or_else(|| {
-                error!(
-                    lexer_input.clone(),
-                    input
-                        .next()
-                        .map_or(input.tokens.last().unwrap().1.clone(), |t| t.1),
-                    "Expected expression"
-                );
-            });
-            Some(Expression::Binary(Box::new(left), op, Box::new(right)))
-        } else {
-            Some(left)
+    /// The name this statement declares, if it's a declaration.
+    pub fn name(&self) -> Option<&str> {
+        match self.inner() {
+            Statement::FunctionDecl { name, .. }
+            | Statement::ClassDecl { name, .. }
+            | Statement::InterfaceDecl { name, .. }
+            | Statement::EnumDecl { name, .. } => Some(name),
+            Statement::VariableDecl { pattern, .. } => match &**pattern {
+                Pattern::Binding(name) => Some(name),
+                _ => None,
+            },
+            _ => None,
         }
     }
-    pub fn factor(input: &mut ParserInput, lexer_input: &mut LexerInput) -> Option<Expression> {
-        let left = Expression::unary(input, lexer_input)?;
-        if input.peek(|t| matches!(t, Token::Mul | Token::Div | Token::Mod)) {
-            let op = input.next().unwrap().0;
-            let right = Expression::unary(input, lexer_input).unwrap_or_else(|| {
-                error!(
-                    lexer_input.clone(),
-                    input
-                        .next()
-                        .map_or(input.tokens.last().unwrap().1.clone(), |t| t.1),
-                    "Expected expression"
-                );
-            });
-            Some(Expression::Binary(Box::new(left), op, Box::new(right)))
+    pub fn visibility(&self) -> Option<Visibility> {
+        self.modifiers().and_then(|m| m.visibility)
+    }
+}
+
+/// One of the comma-separated items of an `import`.
+#[derive(Debug)]
+pub enum Import {
+    /// `Foo.Bar`, or `.widget.Button` which starts at the package of the current file
+    Tree { relative: bool, tree: ImportTree },
+    /// `"./widget.js" as Widget`, a JavaScript module
+    Module {
+        source: String,
+        alias: String,
+        span: Range<usize>,
+    },
+}
+impl Import {
+    fn parse(input: &mut ParserInput) -> Result<Import, Diagnostic> {
+        if input.peek(|t| matches!(t, Token::StringLiteral(_))) {
+            let Some((Token::StringLiteral(source), span)) = input.next() else {
+                unreachable!()
+            };
+            if !input.peek(|t| matches!(t, Token::Keyword(Keyword::As))) {
+                error!(span, "JavaScript modules must be imported with `as`");
+            }
+            input.next();
+            let alias = match input.next() {
+                Some((Token::Identifier(s), _)) => s,
+                Some((_, span)) => error!(span, "Expected identifier"),
+                None => error!(input.next_span(), "Expected identifier"),
+            };
+            return Ok(Import::Module {
+                source,
+                alias,
+                span: span.start..input.prev_end(),
+            });
+        }
+        let relative = input.peek(|t| matches!(t, Token::Dot));
+        if relative {
+            input.next();
+        }
+        let tree = ImportTree::parse(input, relative)?;
+        Ok(Import::Tree { relative, tree })
+    }
+    /// The names this brings into scope, which are unknown for globs.
+    pub fn names(&self) -> Vec<&str> {
+        match self {
+            Import::Tree { tree, .. } => tree
+                .leaves()
+                .into_iter()
+                .filter_map(|(_, leaf)| leaf.name())
+                .collect(),
+            Import::Module { alias, .. } => vec![alias],
+        }
+    }
+}
+
+/// The path of an import, which may branch out into groups.
+#[derive(Debug)]
+pub enum ImportTree {
+    /// `Foo.Bar` or `Foo.Bar as Baz`
+    Path {
+        path: Vec<String>,
+        alias: Option<String>,
+        span: Range<usize>,
+    },
+    /// `Foo.*`, which imports everything public in `Foo`
+    Glob {
+        path: Vec<String>,
+        span: Range<usize>,
+    },
+    /// `Foo.{Bar, Baz.*}`, where each item continues the path of the group
+    Group {
+        prefix: Vec<String>,
+        items: Vec<ImportTree>,
+        span: Range<usize>,
+    },
+}
+impl ImportTree {
+    /// Parses a path, which may only start with `*` or `{` if `bare` is set.
+    fn parse(input: &mut ParserInput, bare: bool) -> Result<ImportTree, Diagnostic> {
+        let start = input.tokens.get(input.pos).map_or(0, |t| t.1.start);
+        let mut path = Vec::new();
+        loop {
+            match input.next() {
+                Some((Token::Identifier(s), _)) => path.push(s),
+                Some((Token::Mul, _)) if bare || !path.is_empty() => {
+                    return Ok(ImportTree::Glob {
+                        path,
+                        span: start..input.prev_end(),
+                    });
+                }
+                Some((Token::OpenBrace, _)) if bare || !path.is_empty() => {
+                    let mut items = Vec::new();
+                    while !input.peek(|t| matches!(t, Token::CloseBrace)) {
+                        items.push(input.nested(|input| ImportTree::parse(input, true))?);
+                        if input.peek(|t| matches!(t, Token::Comma)) {
+                            input.next();
+                        } else {
+                            break;
+                        }
+                    }
+                    if !input.peek(|t| matches!(t, Token::CloseBrace)) {
+                        return Err(input.missing("Expected closing brace", "}"));
+                    }
+                    input.next();
+                    return Ok(ImportTree::Group {
+                        prefix: path,
+                        items,
+                        span: start..input.prev_end(),
+                    });
+                }
+                Some((_, span)) => error!(span, "Expected identifier"),
+                None => error!(input.next_span(), "Expected identifier"),
+            }
+            if !input.peek(|t| matches!(t, Token::Dot)) {
+                break;
+            }
+            input.next();
+        }
+        let alias = if input.peek(|t| matches!(t, Token::Keyword(Keyword::As))) {
+            input.next();
+            match input.next() {
+                Some((Token::Identifier(s), _)) => Some(s),
+                Some((_, span)) => error!(span, "Expected identifier"),
+                None => error!(input.next_span(), "Expected identifier"),
+            }
+        } else {
+            None
+        };
+        Ok(ImportTree::Path {
+            path,
+            alias,
+            span: start..input.prev_end(),
+        })
+    }
+    /// Every path and glob in this tree, with groups expanded into full paths.
+    pub fn leaves(&self) -> Vec<(Vec<String>, &ImportTree)> {
+        match self {
+            ImportTree::Path { path, .. } | ImportTree::Glob { path, .. } => {
+                vec![(path.clone(), self)]
+            }
+            ImportTree::Group { prefix, items, .. } => items
+                .iter()
+                .flat_map(ImportTree::leaves)
+                .map(|(path, leaf)| ([prefix.clone(), path].concat(), leaf))
+                .collect(),
+        }
+    }
+    /// The name a path is imported as.
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            ImportTree::Path { path, alias, .. } => {
+                alias.as_deref().or(path.last().map(String::as_str))
+            }
+            _ => None,
+        }
+    }
+    pub fn span(&self) -> &Range<usize> {
+        match self {
+            ImportTree::Path { span, .. }
+            | ImportTree::Glob { span, .. }
+            | ImportTree::Group { span, .. } => span,
+        }
+    }
+}
+
+/// A variant of an enum, like `Circle(radius: f64)`, `Empty` or `Red = 1`.
+#[derive(Debug)]
+pub struct EnumVariant {
+    pub attributes: Vec<Attribute>,
+    pub name: String,
+    pub fields: Vec<(String, Type)>,
+    /// The value of a variant of a C-like enum, which is always an integer literal
+    pub discriminant: Option<Expression>,
+    pub span: Range<usize>,
+}
+impl EnumVariant {
+    /// Parses the variants of an enum, from the opening to the closing brace.
+    fn parse_list(input: &mut ParserInput) -> Result<Vec<EnumVariant>, Diagnostic> {
+        if !input.peek(|t| matches!(t, Token::OpenBrace)) {
+            error!(input.next_span(), "Expected opening brace");
+        }
+        input.next();
+        let mut variants = Vec::<EnumVariant>::new();
+        while let Some(variant) = EnumVariant::parse(input)? {
+            if variants.iter().any(|v| v.name == variant.name) {
+                error!(variant.span, "Duplicate variant `{}`", variant.name);
+            }
+            variants.push(variant);
+            if input.peek(|t| matches!(t, Token::Comma)) {
+                input.next();
+            } else {
+                break;
+            }
+        }
+        if !input.peek(|t| matches!(t, Token::CloseBrace)) {
+            return Err(input.missing("Expected closing brace", "}"));
+        }
+        input.next();
+        // Discriminants are for interop with C-like enums, which have no payloads
+        if variants.iter().any(|v| !v.fields.is_empty()) {
+            if let Some(variant) = variants.iter().find(|v| v.discriminant.is_some()) {
+                error!(
+                    variant.span.clone(),
+                    "Only enums without fields can have discriminants"
+                );
+            }
+        }
+        Ok(variants)
+    }
+    fn parse(input: &mut ParserInput) -> Result<Option<EnumVariant>, Diagnostic> {
+        let mut attributes = Vec::new();
+        while let Some(attribute) = Attribute::parse(input)? {
+            attributes.push(attribute);
+        }
+        if !input.peek(|t| matches!(t, Token::Identifier(_))) {
+            if !attributes.is_empty() {
+                error!(input.next_span(), "Expected variant after attribute");
+            }
+            return Ok(None);
+        }
+        let (Token::Identifier(name), span) = input.next().unwrap() else {
+            unreachable!()
+        };
+        let mut fields = Vec::new();
+        if input.peek(|t| matches!(t, Token::OpenParen)) {
+            input.next();
+            for (pattern, field_type) in Statement::params(input, true)? {
+                let Pattern::Binding(name) = pattern else {
+                    error!(span, "Fields of variants must be named");
+                };
+                fields.push((name, field_type.unwrap()));
+            }
+        }
+        let discriminant = if input.peek(|t| matches!(t, Token::Assign)) {
+            input.next();
+            let start = input.tokens.get(input.pos).map_or(0, |t| t.1.start);
+            let value = Expression::parse(input)?;
+            let span = start..input.prev_end();
+            match value {
+                Some(value @ (Expression::SignedLiteral(_) | Expression::UnsignedLiteral(_))) => {
+                    Some(value)
+                }
+                Some(_) => error!(span, "Expected integer discriminant"),
+                None => return Err(input.error("Expected expression")),
+            }
+        } else {
+            None
+        };
+        Ok(Some(EnumVariant {
+            attributes,
+            name,
+            fields,
+            discriminant,
+            span,
+        }))
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Mutability {
+    /// `const`, a value known at compile time
+    Const,
+    /// `let`, which can only be assigned once
+    Immutable,
+    /// `let mut`
+    Mutable,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Visibility {
+    /// `pub`, which exports a top-level declaration from its file
+    Public,
+    /// `private`, which hides a class member from outside of the class
+    Private,
+}
+
+/// Modifiers in front of a declaration, like `pub static`.
+///
+/// Top-level declarations are private to their file unless they're `pub`, while
+/// class members are public unless they're `private`.
+#[derive(Debug)]
+pub struct Modifiers {
+    pub visibility: Option<Visibility>,
+    pub is_static: bool,
+    pub span: Range<usize>,
+}
+impl Modifiers {
+    pub fn parse(input: &mut ParserInput) -> Result<Option<Modifiers>, Diagnostic> {
+        let mut modifiers = Modifiers {
+            visibility: None,
+            is_static: false,
+            span: 0..0,
+        };
+        let mut any = false;
+        while let Some((Token::Keyword(keyword), span)) = input.tokens.get(input.pos).cloned() {
+            let in_class = matches!(
+                input.members,
+                Some(DeclKind::Class | DeclKind::AbstractClass)
+            );
+            match keyword {
+                Keyword::Static => {
+                    if modifiers.is_static {
+                        error!(span, "Duplicate modifier `static`");
+                    }
+                    if !in_class {
+                        error!(span, "Only members of classes can be static");
+                    }
+                    modifiers.is_static = true;
+                }
+                Keyword::Pub | Keyword::Private => {
+                    if modifiers.visibility.is_some() {
+                        error!(span, "Duplicate visibility modifier");
+                    }
+                    if !in_class && input.members != Some(DeclKind::Module) {
+                        error!(
+                            span,
+                            "Only top-level declarations and class members can have a visibility"
+                        );
+                    }
+                    if keyword == Keyword::Private && !in_class {
+                        let end = input
+                            .tokens
+                            .get(input.pos + 1)
+                            .map_or(span.end, |t| t.1.start);
+                        return Err(Diagnostic::error(
+                            span.clone(),
+                            "Top-level declarations are already private unless they're `pub`",
+                        )
+                        .with_suggestion(
+                            "remove `private`",
+                            span.start..end,
+                            "",
+                            Applicability::MachineApplicable,
+                        ));
+                    }
+                    modifiers.visibility = Some(if keyword == Keyword::Pub {
+                        Visibility::Public
+                    } else {
+                        Visibility::Private
+                    });
+                }
+                _ => break,
+            }
+            if !any {
+                modifiers.span.start = span.start;
+            }
+            modifiers.span.end = span.end;
+            any = true;
+            input.next();
+        }
+        Ok(any.then_some(modifiers))
+    }
+}
+
+/// `@name(args)` in front of a statement or enum variant, like `@inline` or
+/// `@wasm_import("env", "log")`.
+///
+/// Attributes aren't keywords, so backends and lints can give meaning to new ones.
+#[derive(Debug)]
+pub struct Attribute {
+    pub name: String,
+    pub args: Vec<Expression>,
+    pub span: Range<usize>,
+}
+impl Attribute {
+    pub fn parse(input: &mut ParserInput) -> Result<Option<Attribute>, Diagnostic> {
+        if !input.peek(|t| matches!(t, Token::At)) {
+            return Ok(None);
+        }
+        let start = input.next().unwrap().1.start;
+        let name = match input.next() {
+            Some((Token::Identifier(s), _)) => s,
+            Some((_, span)) => error!(span, "Expected attribute name"),
+            None => error!(input.next_span(), "Expected attribute name"),
+        };
+        let mut args = Vec::new();
+        if input.peek(|t| matches!(t, Token::OpenParen)) {
+            input.next();
+            while let Some(arg) = Expression::parse(input)? {
+                args.push(arg);
+                if input.peek(|t| matches!(t, Token::Comma)) {
+                    input.next();
+                } else {
+                    break;
+                }
+            }
+            if !input.peek(|t| matches!(t, Token::CloseParen)) {
+                return Err(input.missing("Expected closing paren", ")"));
+            }
+            input.next();
+        }
+        Ok(Some(Attribute {
+            name,
+            args,
+            span: start..input.prev_end(),
+        }))
+    }
+    /// The arguments, if they're all string literals.
+    pub fn string_args(&self) -> Option<Vec<&str>> {
+        self.args
+            .iter()
+            .map(|arg| match arg {
+                Expression::StringLiteral(s) => Some(s.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug)]
+pub enum Expression {
+    Binary(Box<Expression>, Token, Box<Expression>),
+    Unary(Token, Box<Expression>),
+    /// `cond ? then : otherwise`, which only evaluates the chosen branch
+    Conditional(Box<Expression>, Box<Expression>, Box<Expression>),
+    /// `value ?? default`, which is the value unless it's `null`, and only evaluates the
+    /// default then. A value of type `T?` with a default of type `T` gives a `T`.
+    Coalesce(Box<Expression>, Box<Expression>),
+    /// `start..end`, or `start..=end` if it includes the end
+    Range {
+        start: Box<Expression>,
+        end: Box<Expression>,
+        inclusive: bool,
+    },
+    /// `{ statements; value }`, where the value is the last expression if it has no semicolon.
+    Block(Vec<Statement>, Option<Box<Expression>>),
+    /// `if cond { ... } else ...`, where the else branch is either a block or another `if`.
+    If(Box<Expression>, Box<Expression>, Option<Box<Expression>>),
+    /// `'label: while cond { ... }`
+    While(Option<String>, Box<Expression>, Box<Expression>),
+    /// `'label: loop { ... }`
+    Loop(Option<String>, Box<Expression>),
+    /// `'label: for item in iterable { ... }`, where the item may be destructured
+    For(
+        Option<String>,
+        Box<Pattern>,
+        Box<Expression>,
+        Box<Expression>,
+    ),
+    /// `break 'label value`, where the value is only allowed in `loop`
+    Break(Option<String>, Option<Box<Expression>>),
+    /// `continue 'label`
+    Continue(Option<String>),
+    /// `try { ... } catch (error) { ... } finally { ... }`, with a catch, a finally or both.
+    /// Its value is the value of the try block, or of the catch block if an error was
+    /// thrown and caught. The finally block always runs last, and its value is ignored.
+    Try {
+        body: Box<Expression>,
+        catch: Option<Box<Catch>>,
+        finally: Option<Box<Expression>>,
+    },
+    /// `throw error`, which exits to the innermost `catch`
+    Throw(Box<Expression>),
+    /// `match value { pattern if guard => expr, ... }`
+    Match(Box<Expression>, Vec<MatchArm>),
+    /// `(x: u8) -> x * 2` or `function(x) -> u8 { ... }`, where `captures` are the
+    /// names the body uses without binding them, in order of first use.
+    Lambda {
+        params: Vec<(Pattern, Option<Type>)>,
+        return_type: Option<Type>,
+        body: Box<Expression>,
+        captures: Vec<String>,
+    },
+    /// `[1, 2, ...rest]`
+    Array(Vec<Expression>),
+    /// `{ key: value, "other key": value }`. There's no empty map literal, since `{}` is always
+    /// an empty block.
+    Map(Vec<(String, Expression)>),
+    /// `...items`, which is only allowed in an array literal
+    Spread(Box<Expression>),
+    /// `callee(args)` or `callee<type_args>(args)`
+    Call {
+        callee: Box<Expression>,
+        type_args: Vec<Type>,
+        args: Vec<Expression>,
+    },
+    /// `object.field`, or `object?.field` if optional. An optional member of a `null`
+    /// object is `null` and skips the rest of the chain, so `user?.profile.name` has
+    /// type `T?` where `name` is a `T`.
+    Member {
+        object: Box<Expression>,
+        field: String,
+        optional: bool,
+    },
+    /// `object[index]`
+    Index {
+        object: Box<Expression>,
+        index: Box<Expression>,
+    },
+    Identifier(String),
+    /// `super`, the superclass of the enclosing class
+    Super,
+    /// `self`, the instance a method was called on
+    SelfValue,
+    /// `new Foo<T>(args)`
+    New {
+        class: Type,
+        args: Vec<Expression>,
+    },
+    StringLiteral(String),
+    SignedLiteral(i64),
+    UnsignedLiteral(u64),
+    FloatLiteral(f64),
+    BoolLiteral(bool),
+    CharLiteral(char),
+    /// `null`, the absence of a value
+    NullLiteral,
+}
+impl Expression {
+    /// Whether this expression ends in a block, and so can be a statement without a semicolon.
+    pub fn is_block_like(&self) -> bool {
+        matches!(
+            self,
+            Expression::Block(_, _)
+                | Expression::If(_, _, _)
+                | Expression::While(_, _, _)
+                | Expression::Loop(_, _)
+                | Expression::For(_, _, _, _)
+                | Expression::Match(_, _)
+                | Expression::Try { .. }
+        )
+    }
+    /// Whether this expression is a dotted path like `Console.log`.
+    pub fn is_path(&self) -> bool {
+        match self {
+            Expression::Identifier(_) => true,
+            Expression::Member { object, .. } => object.is_path(),
+            _ => false,
+        }
+    }
+    /// Whether this expression is part of a chain with `?.` in it, like `user?.profile.name`.
+    pub fn is_optional_chain(&self) -> bool {
+        match self {
+            Expression::Member { optional: true, .. } => true,
+            Expression::Member { object, .. }
+            | Expression::Index { object, .. }
+            | Expression::Call { callee: object, .. } => object.is_optional_chain(),
+            _ => false,
+        }
+    }
+    pub fn parse(input: &mut ParserInput) -> Result<Option<Expression>, Diagnostic> {
+        if input.eof() {
+            return Ok(None);
+        }
+        // Statements in blocks aren't members, even inside of a class
+        let members = input.members.take();
+        let expr = input.nested(Expression::conditional);
+        input.members = members;
+        expr
+    }
+    /// `cond ? then : otherwise`, which is right-associative so it can be chained.
+    pub fn conditional(input: &mut ParserInput) -> Result<Option<Expression>, Diagnostic> {
+        let Some(cond) = Expression::range(input)? else {
+            return Ok(None);
+        };
+        if input.peek(|t| matches!(t, Token::Question)) {
+            Expression::branches(input, cond).map(Some)
         } else {
-            Some(left)
+            Ok(Some(cond))
+        }
+    }
+    /// `? then : otherwise` after the condition of a conditional expression.
+    fn branches(input: &mut ParserInput, cond: Expression) -> Result<Expression, Diagnostic> {
+        input.next();
+        let then = Expression::parse(input)?.ok_or_else(|| input.error("Expected expression"))?;
+        if !input.peek(|t| matches!(t, Token::Colon)) {
+            return Err(input.missing("Expected colon", ":"));
+        }
+        input.next();
+        let otherwise = input
+            .nested(Expression::conditional)?
+            .ok_or_else(|| input.error("Expected expression"))?;
+        Ok(Expression::Conditional(
+            Box::new(cond),
+            Box::new(then),
+            Box::new(otherwise),
+        ))
+    }
+    pub fn range(input: &mut ParserInput) -> Result<Option<Expression>, Diagnostic> {
+        let Some(start) = Expression::binary(input, 0)? else {
+            return Ok(None);
+        };
+        if !input.peek(|t| matches!(t, Token::DotDot | Token::DotDotEq)) {
+            return Ok(Some(start));
+        }
+        let inclusive = matches!(input.next().unwrap().0, Token::DotDotEq);
+        let end =
+            Expression::binary(input, 0)?.ok_or_else(|| input.error("Expected end of range"))?;
+        Ok(Some(Expression::Range {
+            start: Box::new(start),
+            end: Box::new(end),
+            inclusive,
+        }))
+    }
+    /// Binary operators binding tighter than `min`, folded by precedence so that
+    /// `a + b * c - d` is `(a + (b * c)) - d`.
+    pub fn binary(input: &mut ParserInput, min: u8) -> Result<Option<Expression>, Diagnostic> {
+        let start = input.tokens.get(input.pos).map_or(0, |t| t.1.start);
+        let outer = std::mem::replace(&mut input.chain, 0);
+        let Some(mut left) = Expression::unary(input)? else {
+            input.chain = outer;
+            return Ok(None);
+        };
+        while let Some(precedence) = input
+            .tokens
+            .get(input.pos)
+            .and_then(|t| t.0.precedence())
+            .filter(|&p| p > min)
+        {
+            let op = input.next().unwrap().0;
+            // Right-associative operators take operators of the same precedence on their right
+            let min = if op == Token::Pow {
+                precedence - 1
+            } else {
+                precedence
+            };
+            let right = input
+                .nested(|input| Expression::binary(input, min))?
+                .ok_or_else(|| input.error("Expected expression"))?;
+            left = if op == Token::Coalesce {
+                Expression::Coalesce(Box::new(left), Box::new(right))
+            } else {
+                Expression::Binary(Box::new(left), op, Box::new(right))
+            };
+            input.chain += 1;
+            if input.chain > MAX_CHAIN {
+                error!(start..input.prev_end(), "Expression is too long");
+            }
         }
+        input.chain = input.chain.max(outer);
+        Ok(Some(left))
     }
-    pub fn unary(input: &mut ParserInput, lexer_input: &mut LexerInput) -> Option<Expression> {
+    pub fn unary(input: &mut ParserInput) -> Result<Option<Expression>, Diagnostic> {
         if input.peek(Token::is_unary_op) {
-            Some(Expression::Unary(
-                input.next().unwrap().0,
-                Box::new(Expression::unary(input, lexer_input)?),
-            ))
+            let start = input.tokens.get(input.pos).map_or(0, |t| t.1.start);
+            let op = input.next().unwrap().0;
+            let Some(expr) = input.nested(Expression::unary)? else {
+                return Ok(None);
+            };
+            match op {
+                Token::Sub => Expression::negate(expr, start..input.prev_end()).map(Some),
+                op => Ok(Some(Expression::Unary(op, Box::new(expr)))),
+            }
         } else {
-            Expression::grouping(input, lexer_input)
+            Expression::lambda(input)
+        }
+    }
+    /// `-expr`, where the sign of a number literal is folded into it, so that `-1` is a
+    /// literal that can go down to `i64::MIN`. Folding happens here rather than in the
+    /// lexer so that `-2 ** 2` groups like `-x ** 2`.
+    fn negate(expr: Expression, span: Range<usize>) -> Result<Expression, Diagnostic> {
+        Ok(match expr {
+            Expression::UnsignedLiteral(n) => match 0i64.checked_sub_unsigned(n) {
+                Some(n) => Expression::SignedLiteral(n),
+                None => error!(span, "Integer literal is too small"),
+            },
+            Expression::FloatLiteral(n) => Expression::FloatLiteral(-n),
+            expr => Expression::Unary(Token::Sub, Box::new(expr)),
+        })
+    }
+    pub fn lambda(input: &mut ParserInput) -> Result<Option<Expression>, Diagnostic> {
+        let (params, return_type) =
+            if input.peek(|t| matches!(t, Token::Keyword(Keyword::Function))) {
+                input.next();
+                if !input.peek(|t| matches!(t, Token::OpenParen)) {
+                    return Err(input.missing("Expected opening paren", "("));
+                }
+                input.next();
+                let params = Statement::params(input, false)?;
+                let return_type = if input.peek(|t| matches!(t, Token::Arrow)) {
+                    input.next();
+                    Some(Type::parse(input)?)
+                } else {
+                    None
+                };
+                (params, return_type)
+            } else if Expression::is_arrow_lambda(input) {
+                input.next();
+                let params = Statement::params(input, false)?;
+                // The arrow is known to follow
+                input.next();
+                (params, None)
+            } else {
+                return Expression::postfix(input);
+            };
+        let body = Statement::function_body(input, input.in_method)?;
+        // The walk is recursive, but `MAX_DEPTH` and `MAX_CHAIN` already bound how deep the
+        // body is
+        let captures = captures::free_variables(&params, &body);
+        Ok(Some(Expression::Lambda {
+            params,
+            return_type,
+            body: Box::new(body),
+            captures,
+        }))
+    }
+    /// Whether the next tokens are parenthesized parameters followed by an arrow, like `(x) ->`.
+    fn is_arrow_lambda(input: &ParserInput) -> bool {
+        if !input.peek_nth(0, |t| matches!(t, Token::OpenParen)) {
+            return false;
+        }
+        let mut depth = 0;
+        for (i, (token, _)) in input.tokens[input.pos..].iter().enumerate() {
+            match token {
+                Token::OpenParen => depth += 1,
+                Token::CloseParen => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return input.peek_nth(i + 1, |t| matches!(t, Token::Arrow));
+                    }
+                }
+                _ => {}
+            }
         }
+        false
     }
-    pub fn grouping(input: &mut ParserInput, lexer_input: &mut LexerInput) -> Option<Expression> {
+    pub fn postfix(input: &mut ParserInput) -> Result<Option<Expression>, Diagnostic> {
+        let outer = std::mem::replace(&mut input.chain, 0);
+        let expr = match Expression::grouping(input)? {
+            // Block-like expressions end a statement, so `if a { ... } (b)` isn't a call
+            Some(expr) if !expr.is_block_like() => Some(Expression::postfix_ops(input, expr)?),
+            expr => expr,
+        };
+        input.chain = input.chain.max(outer);
+        Ok(expr)
+    }
+    /// Applies calls, member accesses and indexing to `expr`, like `getUser().name`.
+    fn postfix_ops(
+        input: &mut ParserInput,
+        mut expr: Expression,
+    ) -> Result<Expression, Diagnostic> {
+        loop {
+            let start = input.tokens.get(input.pos).map_or(0, |t| t.1.start);
+            expr = if input.peek(|t| matches!(t, Token::OpenParen)) {
+                input.next();
+                Expression::Call {
+                    callee: Box::new(expr),
+                    type_args: Vec::new(),
+                    args: Expression::arguments(input)?,
+                }
+            } else if expr.is_path() && Expression::is_type_args(input) {
+                input.next();
+                let type_args = Type::parse_args(input)?;
+                // `is_type_args` made sure the arguments are followed by a paren
+                input.next();
+                Expression::Call {
+                    callee: Box::new(expr),
+                    type_args,
+                    args: Expression::arguments(input)?,
+                }
+            } else if input.peek(|t| matches!(t, Token::Dot | Token::QuestionDot)) {
+                let optional = input.next().unwrap().0 == Token::QuestionDot;
+                Expression::Member {
+                    object: Box::new(expr),
+                    field: input.member_name()?,
+                    optional,
+                }
+            } else if input.peek(|t| matches!(t, Token::OpenBracket)) {
+                input.next();
+                let index =
+                    Expression::parse(input)?.ok_or_else(|| input.error("Expected expression"))?;
+                if !input.peek(|t| matches!(t, Token::CloseBracket)) {
+                    return Err(input.missing("Expected closing bracket", "]"));
+                }
+                input.next();
+                Expression::Index {
+                    object: Box::new(expr),
+                    index: Box::new(index),
+                }
+            } else {
+                return Ok(expr);
+            };
+            input.chain += 1;
+            if input.chain > MAX_CHAIN {
+                error!(start..input.prev_end(), "Chain is too long");
+            }
+        }
+    }
+    /// Whether the next tokens are type arguments of a call like `parse<u8>(s)`, rather
+    /// than a comparison. They are if the `<` is closed by a `>` that is followed by
+    /// a paren, with nothing but tokens that can appear in types in between.
+    fn is_type_args(input: &ParserInput) -> bool {
+        if !input.peek_nth(0, |t| matches!(t, Token::Lt)) {
+            return false;
+        }
+        // Open angle brackets for each level of parens or brackets, innermost last
+        let mut angles = vec![0];
+        for (i, (token, _)) in input.tokens[input.pos..].iter().enumerate() {
+            let closed = match token {
+                Token::Gt => 1,
+                Token::Shr => 2,
+                Token::Lt => {
+                    *angles.last_mut().unwrap() += 1;
+                    continue;
+                }
+                Token::OpenParen | Token::OpenBracket => {
+                    angles.push(0);
+                    continue;
+                }
+                Token::CloseParen | Token::CloseBracket if angles.len() > 1 => {
+                    if angles.pop() != Some(0) {
+                        return false;
+                    }
+                    continue;
+                }
+                Token::Identifier(_)
+                | Token::Dot
+                | Token::Comma
+                | Token::Arrow
+                | Token::Question => continue,
+                _ => return false,
+            };
+            let open = angles.last_mut().unwrap();
+            if closed > *open {
+                return false;
+            }
+            *open -= closed;
+            if angles == [0] {
+                return input.peek_nth(i + 1, |t| matches!(t, Token::OpenParen));
+            }
+        }
+        false
+    }
+    /// The arguments of a call, after the opening paren.
+    fn arguments(input: &mut ParserInput) -> Result<Vec<Expression>, Diagnostic> {
+        let mut args = Vec::new();
+        while let Some(arg) = Expression::parse(input)? {
+            args.push(arg);
+            if input.peek(|t| matches!(t, Token::CloseParen)) {
+                break;
+            }
+            if input.peek(|t| matches!(t, Token::Comma)) {
+                input.next();
+            } else if input.peek(Token::begins_expression) {
+                return Err(input.missing("Expected comma", ","));
+            } else {
+                break;
+            }
+        }
+        if !input.peek(|t| matches!(t, Token::CloseParen)) {
+            return Err(input.missing("Expected closing paren", ")"));
+        }
+        input.next();
+        Ok(args)
+    }
+    pub fn grouping(input: &mut ParserInput) -> Result<Option<Expression>, Diagnostic> {
         if input.peek(|t| matches!(t, Token::OpenParen)) {
             input.next();
-            let expr = Expression::parse(input, lexer_input)?;
+            let Some(expr) = Expression::parse(input)? else {
+                return Ok(None);
+            };
             if input.peek(|t| matches!(t, Token::CloseParen)) {
                 input.next();
-                Some(expr)
+                Ok(Some(expr))
             } else {
+                Err(input.missing("Expected closing parenthesis", ")"))
+            }
+        } else {
+            Expression::if_else(input)
+        }
+    }
+    pub fn if_else(input: &mut ParserInput) -> Result<Option<Expression>, Diagnostic> {
+        if input.peek(|t| matches!(t, Token::Keyword(Keyword::If))) {
+            Expression::if_expr(input).map(Some)
+        } else {
+            Expression::try_catch(input)
+        }
+    }
+    /// `if cond { ... } else ...`, starting at the `if`.
+    ///
+    /// `else if` arms are parsed in a loop rather than nesting, and only folded into the
+    /// `else` of the arm before them afterwards. That nests the tree like a chain of
+    /// operators, so the arms count toward `MAX_CHAIN` instead of `MAX_DEPTH`.
+    fn if_expr(input: &mut ParserInput) -> Result<Expression, Diagnostic> {
+        let start = input.tokens.get(input.pos).map_or(0, |t| t.1.start);
+        let outer = input.chain;
+        let mut height = 0;
+        let mut arms = Vec::new();
+        let mut otherwise = None;
+        loop {
+            input.next();
+            input.chain = 0;
+            let cond =
+                Expression::parse(input)?.ok_or_else(|| input.error("Expected condition"))?;
+            if !input.peek(|t| matches!(t, Token::OpenBrace)) {
+                error!(input.next_span(), "Expected opening brace");
+            }
+            let then = Expression::block(input)?.unwrap();
+            height = height.max(input.chain + arms.len());
+            arms.push((cond, then));
+            if !input.peek(|t| matches!(t, Token::Keyword(Keyword::Else))) {
+                break;
+            }
+            input.next();
+            if input.peek(|t| matches!(t, Token::OpenBrace)) {
+                input.chain = 0;
+                otherwise = Expression::block(input)?;
+                height = height.max(input.chain + arms.len());
+                break;
+            } else if !input.peek(|t| matches!(t, Token::Keyword(Keyword::If))) {
                 error!(
-                    lexer_input.clone(),
-                    input
-                        .next()
-                        .map_or(input.tokens.last().unwrap().1.clone(), |t| t.1),
-                    "Expected closing parenthesis"
+                    input.next_span(),
+                    "Expected `if` or opening brace after `else`"
                 );
             }
+        }
+        if height > MAX_CHAIN {
+            error!(start..input.prev_end(), "Too many `else if` branches");
+        }
+        input.chain = outer.max(height);
+        let otherwise = arms
+            .into_iter()
+            .rev()
+            .fold(otherwise, |otherwise, (cond, then)| {
+                Some(Expression::If(
+                    Box::new(cond),
+                    Box::new(then),
+                    otherwise.map(Box::new),
+                ))
+            });
+        Ok(otherwise.unwrap())
+    }
+    pub fn try_catch(input: &mut ParserInput) -> Result<Option<Expression>, Diagnostic> {
+        if input.peek(|t| matches!(t, Token::Keyword(Keyword::Try))) {
+            Expression::try_expr(input).map(Some)
+        } else if input.peek(|t| matches!(t, Token::Keyword(Keyword::Throw))) {
+            Expression::throw(input).map(Some)
+        } else if input.peek(|t| matches!(t, Token::Keyword(Keyword::Catch | Keyword::Finally))) {
+            let Some((Token::Keyword(keyword), span)) = input.next() else {
+                unreachable!()
+            };
+            error!(span, "`{}` without a `try`", keyword.as_str());
+        } else {
+            Expression::match_expr(input)
+        }
+    }
+    /// `throw error`, starting at the keyword.
+    fn throw(input: &mut ParserInput) -> Result<Expression, Diagnostic> {
+        input.next();
+        let error =
+            Expression::parse(input)?.ok_or_else(|| input.error("Expected error to throw"))?;
+        Ok(Expression::Throw(Box::new(error)))
+    }
+    /// `try { ... } catch (error) { ... } finally { ... }`, starting at the `try`.
+    fn try_expr(input: &mut ParserInput) -> Result<Expression, Diagnostic> {
+        let span = input.next().unwrap().1;
+        if !input.peek(|t| matches!(t, Token::OpenBrace)) {
+            error!(input.next_span(), "Expected opening brace");
+        }
+        let body = Expression::block(input)?.unwrap();
+        let catch = if input.peek(|t| matches!(t, Token::Keyword(Keyword::Catch))) {
+            input.next();
+            let pattern = if input.peek(|t| matches!(t, Token::OpenParen)) {
+                input.next();
+                let pattern = Pattern::irrefutable(input, "`catch`")?;
+                if !input.peek(|t| matches!(t, Token::CloseParen)) {
+                    return Err(input.missing("Expected closing paren", ")"));
+                }
+                input.next();
+                Some(pattern)
+            } else {
+                None
+            };
+            if !input.peek(|t| matches!(t, Token::OpenBrace)) {
+                error!(input.next_span(), "Expected opening brace");
+            }
+            let body = Expression::block(input)?.unwrap();
+            Some(Box::new(Catch { pattern, body }))
+        } else {
+            None
+        };
+        let finally = if input.peek(|t| matches!(t, Token::Keyword(Keyword::Finally))) {
+            input.next();
+            if !input.peek(|t| matches!(t, Token::OpenBrace)) {
+                error!(input.next_span(), "Expected opening brace");
+            }
+            let outer = input.finally.replace(input.loops.len());
+            let body = Expression::block(input);
+            input.finally = outer;
+            Some(Box::new(body?.unwrap()))
+        } else {
+            None
+        };
+        if catch.is_none() && finally.is_none() {
+            error!(span, "Expected `catch` or `finally` after `try` block");
+        }
+        Ok(Expression::Try {
+            body: Box::new(body),
+            catch,
+            finally,
+        })
+    }
+    pub fn match_expr(input: &mut ParserInput) -> Result<Option<Expression>, Diagnostic> {
+        if input.peek(|t| matches!(t, Token::Keyword(Keyword::Match))) {
+            input.next();
+            let value =
+                Expression::parse(input)?.ok_or_else(|| input.error("Expected expression"))?;
+            let arms = MatchArm::parse_list(input)?;
+            Ok(Some(Expression::Match(Box::new(value), arms)))
+        } else {
+            Expression::looping(input)
+        }
+    }
+    pub fn looping(input: &mut ParserInput) -> Result<Option<Expression>, Diagnostic> {
+        let label = if input.peek(|t| matches!(t, Token::Label(_))) {
+            let Some((Token::Label(label), _)) = input.next() else {
+                unreachable!()
+            };
+            if !input.peek(|t| matches!(t, Token::Colon)) {
+                return Err(input.missing("Expected colon", ":"));
+            }
+            input.next();
+            if !input.peek(|t| {
+                matches!(
+                    t,
+                    Token::Keyword(Keyword::While | Keyword::Loop | Keyword::For)
+                )
+            }) {
+                error!(input.next_span(), "Expected loop after label");
+            }
+            Some(label)
+        } else {
+            None
+        };
+        if input.peek(|t| {
+            matches!(
+                t,
+                Token::Keyword(Keyword::While | Keyword::Loop | Keyword::For)
+            )
+        }) {
+            Expression::loop_expr(input, label).map(Some)
+        } else if input.peek(|t| matches!(t, Token::Keyword(Keyword::Break | Keyword::Continue))) {
+            Expression::jump(input).map(Some)
+        } else {
+            Expression::map(input)
+        }
+    }
+    /// A `while`, `loop` or `for` loop, starting at its keyword.
+    fn loop_expr(input: &mut ParserInput, label: Option<String>) -> Result<Expression, Diagnostic> {
+        match input.next().unwrap().0 {
+            Token::Keyword(Keyword::While) => {
+                let cond =
+                    Expression::parse(input)?.ok_or_else(|| input.error("Expected condition"))?;
+                let body = input.in_loop(label.clone(), LoopKind::While, Expression::loop_body)?;
+                Ok(Expression::While(label, Box::new(cond), Box::new(body)))
+            }
+            Token::Keyword(Keyword::Loop) => {
+                let body = input.in_loop(label.clone(), LoopKind::Loop, Expression::loop_body)?;
+                Ok(Expression::Loop(label, Box::new(body)))
+            }
+            _ => {
+                let item = Pattern::irrefutable(input, "`for` loops")?;
+                if !input.peek(|t| matches!(t, Token::Keyword(Keyword::In))) {
+                    error!(input.next_span(), "Expected `in`");
+                }
+                input.next();
+                let iterable =
+                    Expression::parse(input)?.ok_or_else(|| input.error("Expected expression"))?;
+                let body = input.in_loop(label.clone(), LoopKind::For, Expression::loop_body)?;
+                Ok(Expression::For(
+                    label,
+                    Box::new(item),
+                    Box::new(iterable),
+                    Box::new(body),
+                ))
+            }
+        }
+    }
+    /// `break` or `continue`, starting at its keyword.
+    fn jump(input: &mut ParserInput) -> Result<Expression, Diagnostic> {
+        let (token, span) = input.next().unwrap();
+        let label = Expression::jump_label(input);
+        if token == Token::Keyword(Keyword::Continue) {
+            input.find_loop("continue", &label, span)?;
+            return Ok(Expression::Continue(label));
+        }
+        let kind = input.find_loop("break", &label, span.clone())?;
+        let value = if input.peek(Token::begins_expression) {
+            Some(Box::new(
+                Expression::parse(input)?.ok_or_else(|| input.error("Expected expression"))?,
+            ))
         } else {
-            Expression::block(input, lexer_input)
+            None
+        };
+        if value.is_some() && kind != LoopKind::Loop {
+            error!(span, "`break` with a value is only allowed in `loop`");
+        }
+        Ok(Expression::Break(label, value))
+    }
+    /// The optional label after `break` or `continue`.
+    fn jump_label(input: &mut ParserInput) -> Option<String> {
+        if input.peek(|t| matches!(t, Token::Label(_))) {
+            match input.next() {
+                Some((Token::Label(label), _)) => Some(label),
+                _ => unreachable!(),
+            }
+        } else {
+            None
         }
     }
-    pub fn block(input: &mut ParserInput, lexer_input: &mut LexerInput) -> Option<Expression> {
+    fn loop_body(input: &mut ParserInput) -> Result<Expression, Diagnostic> {
+        if !input.peek(|t| matches!(t, Token::OpenBrace)) {
+            error!(input.next_span(), "Expected opening brace");
+        }
+        Ok(Expression::block(input)?.unwrap())
+    }
+    pub fn map(input: &mut ParserInput) -> Result<Option<Expression>, Diagnostic> {
+        // A key followed by a colon can't start a statement, so that's what tells a map from a
+        // block. Without a key there's nothing to tell them apart, so `{}` is an empty block.
+        // Keys may be keywords, like `{ new: 1 }` in JavaScript.
+        let is_map = input.peek(|t| matches!(t, Token::OpenBrace))
+            && input.peek_nth(1, |t| {
+                matches!(
+                    t,
+                    Token::Identifier(_) | Token::StringLiteral(_) | Token::Keyword(_)
+                )
+            })
+            && input.peek_nth(2, |t| matches!(t, Token::Colon));
+        if !is_map {
+            return Expression::block(input);
+        }
+        input.next();
+        let mut entries = Vec::new();
+        while !input.peek(|t| matches!(t, Token::CloseBrace)) {
+            let key = match input.next() {
+                Some((Token::Identifier(s) | Token::StringLiteral(s), _)) => s,
+                Some((Token::Keyword(keyword), _)) => keyword.as_str().to_string(),
+                Some((_, span)) => error!(span, "Expected key"),
+                None => error!(input.next_span(), "Expected key"),
+            };
+            if !input.peek(|t| matches!(t, Token::Colon)) {
+                return Err(input.missing("Expected colon", ":"));
+            }
+            input.next();
+            let value =
+                Expression::parse(input)?.ok_or_else(|| input.error("Expected expression"))?;
+            entries.push((key, value));
+            if input.peek(|t| matches!(t, Token::Comma)) {
+                input.next();
+            } else if input.peek(|t| matches!(t, Token::Identifier(_) | Token::StringLiteral(_))) {
+                return Err(input.missing("Expected comma", ","));
+            } else {
+                break;
+            }
+        }
+        if !input.peek(|t| matches!(t, Token::CloseBrace)) {
+            return Err(input.missing("Expected closing brace", "}"));
+        }
+        input.next();
+        Ok(Some(Expression::Map(entries)))
+    }
+    pub fn block(input: &mut ParserInput) -> Result<Option<Expression>, Diagnostic> {
         if input.peek(|t| matches!(t, Token::OpenBrace)) {
             input.next();
             let mut statements = Vec::new();
-            while let Some(stmt) = Statement::parse(input, lexer_input) {
-                statements.push(stmt);
+            let mut value = None;
+            while let Some(stmt) = Statement::parse(input)? {
                 if input.peek(|t| matches!(t, Token::CloseBrace)) {
+                    match stmt {
+                        Statement::Expression(expr)
+                            if !input.prev_is(|t| matches!(t, Token::Semicolon)) =>
+                        {
+                            value = Some(Box::new(expr))
+                        }
+                        stmt => statements.push(stmt),
+                    }
                     break;
                 }
+                statements.push(stmt);
             }
             if !input.peek(|t| matches!(t, Token::CloseBrace)) {
-                error!(
-                    lexer_input.clone(),
-                    input
-                        .next()
-                        .map_or(input.tokens.last().unwrap().1.clone(), |t| t.1),
-                    "Expected '}}'"
-                );
+                return Err(input.missing("Expected '}'", "}"));
             } else {
                 input.next();
             }
-            Some(Expression::Block(statements))
+            Ok(Some(Expression::Block(statements, value)))
         } else {
-            Expression::function_call(input, lexer_input)
+            Expression::array(input)
         }
     }
-    pub fn function_call(
-        input: &mut ParserInput,
-        lexer_input: &mut LexerInput,
-    ) -> Option<Expression> {
-        let expr = Expression::path(input, lexer_input)?;
-        match expr {
-            Expression::Path(path) => {
-                if input.peek(|t| matches!(t, Token::OpenParen)) {
-                    let mut args = Vec::new();
+    pub fn array(input: &mut ParserInput) -> Result<Option<Expression>, Diagnostic> {
+        if !input.peek(|t| matches!(t, Token::OpenBracket)) {
+            return Expression::identifier(input);
+        }
+        input.next();
+        let mut items = Vec::new();
+        while !input.peek(|t| matches!(t, Token::CloseBracket)) {
+            if input.peek(|t| matches!(t, Token::Ellipsis)) {
+                input.next();
+                let item =
+                    Expression::parse(input)?.ok_or_else(|| input.error("Expected expression"))?;
+                items.push(Expression::Spread(Box::new(item)));
+            } else if let Some(item) = Expression::parse(input)? {
+                items.push(item);
+            } else {
+                break;
+            }
+            if input.peek(|t| matches!(t, Token::Comma)) {
+                input.next();
+            } else if input.peek(|t| t.begins_expression() || matches!(t, Token::Ellipsis)) {
+                return Err(input.missing("Expected comma", ","));
+            } else {
+                break;
+            }
+        }
+        if !input.peek(|t| matches!(t, Token::CloseBracket)) {
+            return Err(input.missing("Expected closing bracket", "]"));
+        }
+        input.next();
+        Ok(Some(Expression::Array(items)))
+    }
+    pub fn identifier(input: &mut ParserInput) -> Result<Option<Expression>, Diagnostic> {
+        if input.peek(|t| matches!(t, Token::Identifier(_))) {
+            match input.next().unwrap().0 {
+                Token::Identifier(s) => Ok(Some(Expression::Identifier(s))),
+                _ => unreachable!(),
+            }
+        } else if input.peek(|t| matches!(t, Token::Keyword(Keyword::SelfValue))) {
+            let span = input.next().unwrap().1;
+            if !input.in_method {
+                error!(span, "`self` outside of an instance method");
+            }
+            Ok(Some(Expression::SelfValue))
+        } else if input.peek(|t| matches!(t, Token::Keyword(Keyword::New))) {
+            input.next();
+            let class = Type::parse(input)?;
+            if !matches!(class, Type::Named(..)) {
+                error!(input.next_span(), "Expected class name");
+            }
+            if !input.peek(|t| matches!(t, Token::OpenParen)) {
+                return Err(input.missing("Expected opening paren", "("));
+            }
+            input.next();
+            let args = Expression::arguments(input)?;
+            Ok(Some(Expression::New { class, args }))
+        } else if input.peek(|t| matches!(t, Token::Keyword(Keyword::Super))) {
+            let span = input.next().unwrap().1;
+            if !input.in_subclass {
+                error!(span, "`super` outside of a class that extends another");
+            }
+            Ok(Some(Expression::Super))
+        } else {
+            Expression::primary(input)
+        }
+    }
+    pub fn primary(input: &mut ParserInput) -> Result<Option<Expression>, Diagnostic> {
+        if input.peek(Token::is_literal) {
+            match input.next().unwrap().0 {
+                Token::StringLiteral(s) => Ok(Some(Expression::StringLiteral(s))),
+                Token::UnsignedLiteral(s) => Ok(Some(Expression::UnsignedLiteral(s))),
+                Token::FloatLiteral(s) => Ok(Some(Expression::FloatLiteral(s))),
+                Token::CharLiteral(c) => Ok(Some(Expression::CharLiteral(c))),
+                Token::Keyword(Keyword::True) => Ok(Some(Expression::BoolLiteral(true))),
+                Token::Keyword(Keyword::False) => Ok(Some(Expression::BoolLiteral(false))),
+                Token::Keyword(Keyword::Null) => Ok(Some(Expression::NullLiteral)),
+                _ => unreachable!(),
+            }
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Type {
+    /// `u8`, `Foo.Bar` or `List<u8>`
+    Named(Vec<String>, Vec<Type>),
+    /// `[u8]`
+    Array(Box<Type>),
+    /// `u8?`
+    Optional(Box<Type>),
+    /// `(u8, string)`, where `()` is the unit type and `(u8,)` has one element
+    Tuple(Vec<Type>),
+    /// `(u8, string) -> bool`
+    Function(Vec<Type>, Box<Type>),
+}
+impl Type {
+    /// Parses type arguments up to and including the closing `>`, like `u8, string>`.
+    fn parse_args(input: &mut ParserInput) -> Result<Vec<Type>, Diagnostic> {
+        let mut args = Vec::new();
+        loop {
+            args.push(Type::parse(input)?);
+            if input.peek(|t| matches!(t, Token::Comma)) {
+                input.next();
+            } else {
+                break;
+            }
+        }
+        if !input.split_gt() {
+            return Err(input.missing("Expected '>'", ">"));
+        }
+        Ok(args)
+    }
+    pub fn parse(input: &mut ParserInput) -> Result<Type, Diagnostic> {
+        input.nested(|input| {
+            let mut ty = Type::primary(input)?;
+            // `T??` is lexed as a single `??`
+            while input.peek(|t| matches!(t, Token::Question | Token::Coalesce)) {
+                if input.next().unwrap().0 == Token::Coalesce {
+                    ty = Type::Optional(Box::new(ty));
+                }
+                ty = Type::Optional(Box::new(ty));
+            }
+            Ok(ty)
+        })
+    }
+    fn primary(input: &mut ParserInput) -> Result<Type, Diagnostic> {
+        match input.next() {
+            Some((Token::Identifier(name), _)) => {
+                let path = Pattern::path(input, vec![name])?;
+                let args = if input.peek(|t| matches!(t, Token::Lt)) {
                     input.next();
-                    while let Some(arg) = Expression::parse(input, lexer_input) {
-                        args.push(arg);
-                        if input.peek(|t| matches!(t, Token::CloseParen)) {
-                            break;
-                        }
-                        if !input.peek(|t| matches!(t, Token::Comma)) {
-                            error!(
-                                lexer_input.clone(),
-                                input
-                                    .next()
-                                    .map_or(input.tokens.last().unwrap().1.clone(), |t| t.1),
-                                "Expected comma"
-                            );
-                        } else {
-                            input.next();
-                        }
-                    }
-                    if !input.peek(|t| matches!(t, Token::CloseParen)) {
-                        error!(
-                            lexer_input.clone(),
-                            input
-                                .next()
-                                .map_or(input.tokens.last().unwrap().1.clone(), |t| t.1),
-                            "Expected closing paren"
-                        );
-                    } else {
+                    Type::parse_args(input)?
+                } else {
+                    Vec::new()
+                };
+                Ok(Type::Named(path, args))
+            }
+            Some((Token::OpenBracket, _)) => {
+                let element = Type::parse(input)?;
+                if !input.peek(|t| matches!(t, Token::CloseBracket)) {
+                    return Err(input.missing("Expected closing bracket", "]"));
+                }
+                input.next();
+                Ok(Type::Array(Box::new(element)))
+            }
+            Some((Token::OpenParen, _)) => {
+                let mut elements = Vec::new();
+                let mut trailing_comma = false;
+                while !input.peek(|t| matches!(t, Token::CloseParen)) {
+                    elements.push(Type::parse(input)?);
+                    trailing_comma = input.peek(|t| matches!(t, Token::Comma));
+                    if trailing_comma {
                         input.next();
+                    } else if !input.peek(|t| matches!(t, Token::CloseParen)) {
+                        return Err(input.missing("Expected closing paren", ")"));
                     }
-                    Some(Expression::FunctionCall(path, args))
+                }
+                input.next();
+                if input.peek(|t| matches!(t, Token::Arrow)) {
+                    input.next();
+                    let return_type = Type::parse(input)?;
+                    Ok(Type::Function(elements, Box::new(return_type)))
+                } else if elements.len() == 1 && !trailing_comma {
+                    // Parentheses only group, like in `((u8) -> bool)?`
+                    Ok(elements.pop().unwrap())
                 } else {
-                    Some(Expression::Path(path))
+                    Ok(Type::Tuple(elements))
                 }
             }
-            _ => Some(expr),
+            Some((_, span)) => error!(span, "Expected type"),
+            None => error!(input.next_span(), "Expected type"),
         }
     }
-    pub fn path(input: &mut ParserInput, lexer_input: &mut LexerInput) -> Option<Expression> {
-        let mut nodes = Vec::new();
-        let mut had_dot = false;
-        if input.peek(|t| matches!(t, Token::Identifier(_))) {
-            nodes.push(match input.next().unwrap().0 {
-                Token::Identifier(s) => s,
-                _ => unreachable!(),
+}
+
+/// A type parameter like `T` or `T: Comparable + Hashable`.
+#[derive(Debug)]
+pub struct TypeParam {
+    pub name: String,
+    pub bounds: Vec<Type>,
+}
+impl TypeParam {
+    /// Parses `<T, U: Bound>` if the next token is a `<`.
+    fn parse_list(input: &mut ParserInput) -> Result<Vec<TypeParam>, Diagnostic> {
+        let mut params = Vec::new();
+        if !input.peek(|t| matches!(t, Token::Lt)) {
+            return Ok(params);
+        }
+        input.next();
+        loop {
+            let name = match input.next() {
+                Some((Token::Identifier(s), _)) => s,
+                Some((_, span)) => error!(span, "Expected type parameter"),
+                None => error!(input.next_span(), "Expected type parameter"),
+            };
+            let mut bounds = Vec::new();
+            if input.peek(|t| matches!(t, Token::Colon)) {
+                input.next();
+                bounds.push(Type::parse(input)?);
+                while input.peek(|t| matches!(t, Token::Add)) {
+                    input.next();
+                    bounds.push(Type::parse(input)?);
+                }
+            }
+            params.push(TypeParam { name, bounds });
+            if input.peek(|t| matches!(t, Token::Comma)) {
+                input.next();
+            } else {
+                break;
+            }
+        }
+        if !input.split_gt() {
+            return Err(input.missing("Expected '>'", ">"));
+        }
+        Ok(params)
+    }
+}
+
+/// The `catch (error) { ... }` of a `try`, where the error can be destructured or left out.
+#[derive(Debug)]
+pub struct Catch {
+    pub pattern: Option<Pattern>,
+    pub body: Expression,
+}
+
+#[derive(Debug)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub guard: Option<Expression>,
+    pub body: Expression,
+}
+impl MatchArm {
+    /// The braced arms of a `match`.
+    fn parse_list(input: &mut ParserInput) -> Result<Vec<MatchArm>, Diagnostic> {
+        if !input.peek(|t| matches!(t, Token::OpenBrace)) {
+            error!(input.next_span(), "Expected opening brace");
+        }
+        input.next();
+        let mut arms = Vec::new();
+        while !input.peek(|t| matches!(t, Token::CloseBrace)) {
+            let pattern = Pattern::parse(input)?;
+            let guard = if input.peek(|t| matches!(t, Token::Keyword(Keyword::If))) {
+                input.next();
+                Some(Expression::parse(input)?.ok_or_else(|| input.error("Expected guard"))?)
+            } else {
+                None
+            };
+            if !input.peek(|t| matches!(t, Token::FatArrow)) {
+                return Err(input.missing("Expected `=>`", "=>"));
+            }
+            input.next();
+            let body =
+                Expression::parse(input)?.ok_or_else(|| input.error("Expected expression"))?;
+            let block_like = body.is_block_like();
+            arms.push(MatchArm {
+                pattern,
+                guard,
+                body,
             });
-        } else if let Some(primary) = Expression::primary(input, lexer_input) {
-            return Some(primary);
-        } else {
-            return None;
+            if input.peek(|t| matches!(t, Token::Comma)) {
+                input.next();
+            } else if !block_like && !input.peek(|t| matches!(t, Token::CloseBrace)) {
+                return Err(input.missing("Expected comma", ","));
+            }
         }
-        while let Some(t) = input.next() {
-            match t.0 {
-                Token::Dot => {
-                    if had_dot {
-                        error!(lexer_input.clone(), t.1, "Unexpected dot");
-                    } else {
-                        had_dot = true;
+        input.next();
+        Ok(arms)
+    }
+}
+
+#[derive(Debug)]
+pub enum Pattern {
+    /// `_`
+    Wildcard,
+    /// `name`, which matches anything and binds it
+    Binding(String),
+    /// A literal like `1`, `"a"`, `'a'`, `true` or `null`
+    Literal(Expression),
+    /// `lo..hi` or `lo..=hi`, where either bound may be left out
+    Range(Option<Expression>, Option<Expression>, bool),
+    /// `Shape.Empty`, a path with more than one segment
+    Path(Vec<String>),
+    /// `Shape.Circle(radius)`
+    TupleStruct(Vec<String>, Vec<Pattern>),
+    /// `Point { x, y: 0, .. }`, where `..` ignores the remaining fields
+    Struct(Vec<String>, Vec<(String, Pattern)>, bool),
+    /// `(a, b)`, where `()` is the unit value and `(a,)` has one element
+    Tuple(Vec<Pattern>),
+    /// `[first, ...rest]`
+    Array(Vec<Pattern>),
+    /// `...rest` or `...`, which matches the remaining elements of an array
+    Rest(Option<String>),
+    /// `{ name, age: a, .. }`, which matches the keys of a map or the fields of an object
+    Map(Vec<(String, Pattern)>, bool),
+    /// `a | b`
+    Or(Vec<Pattern>),
+}
+impl Pattern {
+    pub fn parse(input: &mut ParserInput) -> Result<Pattern, Diagnostic> {
+        input.nested(|input| {
+            let mut alternatives = vec![Pattern::single(input)?];
+            while input.peek(|t| matches!(t, Token::BitOr)) {
+                input.next();
+                let start = input.tokens.get(input.pos).map_or(0, |t| t.1.start);
+                let alternative = Pattern::single(input)?;
+                // Every alternative must bind the same names, so the arm can use them
+                let mut expected = alternatives[0].bindings();
+                let mut names = alternative.bindings();
+                expected.sort_unstable();
+                names.sort_unstable();
+                if let Some(name) = expected
+                    .iter()
+                    .chain(&names)
+                    .find(|name| !expected.contains(name) || !names.contains(name))
+                {
+                    error!(
+                        start..input.prev_end(),
+                        "`{}` isn't bound by every alternative", name
+                    );
+                }
+                alternatives.push(alternative);
+            }
+            if alternatives.len() == 1 {
+                Ok(alternatives.pop().unwrap())
+            } else {
+                Ok(Pattern::Or(alternatives))
+            }
+        })
+    }
+    /// Parses a pattern that always matches, as needed in `place`.
+    fn irrefutable(input: &mut ParserInput, place: &str) -> Result<Pattern, Diagnostic> {
+        let start = input.tokens.get(input.pos).map_or(0, |t| t.1.start);
+        let pattern = Pattern::parse(input)?;
+        if !pattern.is_irrefutable() {
+            error!(
+                start..input.prev_end(),
+                "Refutable pattern in {}, use `match` instead", place
+            );
+        }
+        Ok(pattern)
+    }
+    fn single(input: &mut ParserInput) -> Result<Pattern, Diagnostic> {
+        if input.peek(|t| matches!(t, Token::OpenParen)) {
+            input.next();
+            let mut elements = Vec::new();
+            let mut trailing_comma = false;
+            while !input.peek(|t| matches!(t, Token::CloseParen)) {
+                elements.push(Pattern::parse(input)?);
+                trailing_comma = input.peek(|t| matches!(t, Token::Comma));
+                if trailing_comma {
+                    input.next();
+                } else if !input.peek(|t| matches!(t, Token::CloseParen)) {
+                    return Err(input.missing("Expected closing paren", ")"));
+                }
+            }
+            input.next();
+            // `(a)` is just grouping
+            if elements.len() == 1 && !trailing_comma {
+                return Ok(elements.pop().unwrap());
+            }
+            return Ok(Pattern::Tuple(elements));
+        }
+        if input.peek(|t| matches!(t, Token::OpenBracket)) {
+            input.next();
+            let mut elements = Vec::new();
+            while !input.peek(|t| matches!(t, Token::CloseBracket)) {
+                if input.peek(|t| matches!(t, Token::Ellipsis)) {
+                    let span = input.next().unwrap().1;
+                    if elements.iter().any(|e| matches!(e, Pattern::Rest(_))) {
+                        error!(span, "Only one rest pattern is allowed in an array");
                     }
+                    let name = match input.peek(|t| matches!(t, Token::Identifier(_))) {
+                        true => match input.next().unwrap().0 {
+                            Token::Identifier(name) => Some(name),
+                            _ => unreachable!(),
+                        },
+                        false => None,
+                    };
+                    elements.push(Pattern::Rest(name));
+                } else {
+                    elements.push(Pattern::parse(input)?);
                 }
-                Token::Identifier(s) if had_dot => {
-                    had_dot = false;
-                    nodes.push(s);
+                if input.peek(|t| matches!(t, Token::Comma)) {
+                    input.next();
+                } else if !input.peek(|t| matches!(t, Token::CloseBracket)) {
+                    return Err(input.missing("Expected closing bracket", "]"));
                 }
-                _ => {
-                    input.rewind();
-                    return Some(Expression::Path(nodes));
+            }
+            input.next();
+            return Ok(Pattern::Array(elements));
+        }
+        if input.peek(|t| matches!(t, Token::OpenBrace)) {
+            input.next();
+            let (fields, rest) = Pattern::fields(input)?;
+            return Ok(Pattern::Map(fields, rest));
+        }
+        if input.peek(|t| matches!(t, Token::DotDot | Token::DotDotEq)) {
+            let inclusive = matches!(input.next().unwrap().0, Token::DotDotEq);
+            let Some(hi) = Pattern::literal(input)? else {
+                error!(input.next_span(), "Expected literal");
+            };
+            return Ok(Pattern::Range(None, Some(hi), inclusive));
+        }
+        if let Some(lo) = Pattern::literal(input)? {
+            if input.peek(|t| matches!(t, Token::DotDot | Token::DotDotEq)) {
+                let inclusive = matches!(input.next().unwrap().0, Token::DotDotEq);
+                let hi = Pattern::literal(input)?;
+                if inclusive && hi.is_none() {
+                    error!(input.next_span(), "Inclusive ranges need an upper bound");
                 }
+                return Ok(Pattern::Range(Some(lo), hi, inclusive));
             }
+            return Ok(Pattern::Literal(lo));
+        }
+        let path = match input.next() {
+            Some((Token::Identifier(s), _)) => vec![s],
+            Some((_, span)) => error!(span, "Expected pattern"),
+            None => error!(input.next_span(), "Expected pattern"),
+        };
+        let path = Pattern::path(input, path)?;
+        if input.peek(|t| matches!(t, Token::OpenParen)) {
+            input.next();
+            let mut fields = Vec::new();
+            while !input.peek(|t| matches!(t, Token::CloseParen)) {
+                fields.push(Pattern::parse(input)?);
+                if input.peek(|t| matches!(t, Token::Comma)) {
+                    input.next();
+                } else if !input.peek(|t| matches!(t, Token::CloseParen)) {
+                    return Err(input.missing("Expected closing paren", ")"));
+                }
+            }
+            input.next();
+            Ok(Pattern::TupleStruct(path, fields))
+        } else if input.peek(|t| matches!(t, Token::OpenBrace)) {
+            input.next();
+            let (fields, rest) = Pattern::fields(input)?;
+            Ok(Pattern::Struct(path, fields, rest))
+        } else if path.len() > 1 {
+            Ok(Pattern::Path(path))
+        } else if path[0] == "_" {
+            Ok(Pattern::Wildcard)
+        } else {
+            Ok(Pattern::Binding(path.into_iter().next().unwrap()))
         }
-        unreachable!()
     }
-    pub fn primary(input: &mut ParserInput, _: &mut LexerInput) -> Option<Expression> {
-        if input.peek(|t| {
-            matches!(
-                t,
-                Token::StringLiteral(_)
-                    | Token::SignedLiteral(_)
-                    | Token::UnsignedLiteral(_)
-                    | Token::FloatLiteral(_)
-            )
-        }) {
-            match input.next().unwrap().0 {
-                Token::StringLiteral(s) => Some(Expression::StringLiteral(s)),
-                Token::SignedLiteral(s) => Some(Expression::SignedLiteral(s)),
-                Token::UnsignedLiteral(s) => Some(Expression::UnsignedLiteral(s)),
-                Token::FloatLiteral(s) => Some(Expression::FloatLiteral(s)),
-                _ => unreachable!(),
+    /// Parses fields like `x, y: 0, .. }` up to and including the closing brace, and
+    /// whether they end in `..`.
+    fn fields(input: &mut ParserInput) -> Result<(Vec<(String, Pattern)>, bool), Diagnostic> {
+        let mut fields = Vec::new();
+        let mut rest = false;
+        while !input.peek(|t| matches!(t, Token::CloseBrace)) {
+            if input.peek(|t| matches!(t, Token::DotDot)) {
+                input.next();
+                rest = true;
+                if !input.peek(|t| matches!(t, Token::CloseBrace)) {
+                    error!(input.next_span(), "`..` must be the last field");
+                }
+                break;
             }
+            let name = match input.next() {
+                Some((Token::Identifier(s), _)) => s,
+                Some((_, span)) => error!(span, "Expected field name"),
+                None => error!(input.next_span(), "Expected field name"),
+            };
+            // `{ x }` is short for `{ x: x }`
+            let pattern = if input.peek(|t| matches!(t, Token::Colon)) {
+                input.next();
+                Pattern::parse(input)?
+            } else {
+                Pattern::Binding(name.clone())
+            };
+            fields.push((name, pattern));
+            if input.peek(|t| matches!(t, Token::Comma)) {
+                input.next();
+            } else if !input.peek(|t| matches!(t, Token::CloseBrace)) {
+                return Err(input.missing("Expected closing brace", "}"));
+            }
+        }
+        input.next();
+        Ok((fields, rest))
+    }
+    /// The rest of a dotted path, after its first segment.
+    fn path(input: &mut ParserInput, mut path: Vec<String>) -> Result<Vec<String>, Diagnostic> {
+        while input.peek(|t| matches!(t, Token::Dot)) {
+            input.next();
+            path.push(input.member_name()?);
+        }
+        Ok(path)
+    }
+    /// A literal, where numbers may be negative like `-1`.
+    fn literal(input: &mut ParserInput) -> Result<Option<Expression>, Diagnostic> {
+        let start = input.tokens.get(input.pos).map_or(0, |t| t.1.start);
+        let negative = input.peek(|t| matches!(t, Token::Sub))
+            && input.peek_nth(1, |t| {
+                matches!(t, Token::UnsignedLiteral(_) | Token::FloatLiteral(_))
+            });
+        if negative {
+            input.next();
+        } else if !input.peek(Token::is_literal) {
+            return Ok(None);
+        }
+        // Literal tokens always parse as a primary expression
+        let literal = Expression::primary(input)?.unwrap();
+        if negative {
+            Expression::negate(literal, start..input.prev_end()).map(Some)
         } else {
-            None
+            Ok(Some(literal))
+        }
+    }
+    /// Whether this pattern matches every value it's given, so it can be used outside
+    /// of `match`. Destructuring a value of the wrong shape is a type error instead.
+    pub fn is_irrefutable(&self) -> bool {
+        match self {
+            Pattern::Wildcard | Pattern::Binding(_) | Pattern::Rest(_) => true,
+            Pattern::Tuple(elements) | Pattern::Array(elements) => {
+                elements.iter().all(Pattern::is_irrefutable)
+            }
+            // A path like `Shape.Rect` names an enum variant, which only matches some values
+            Pattern::Struct(path, fields, _) if path.len() == 1 => {
+                fields.iter().all(|(_, p)| p.is_irrefutable())
+            }
+            Pattern::Map(fields, _) => fields.iter().all(|(_, p)| p.is_irrefutable()),
+            Pattern::Literal(_)
+            | Pattern::Range(_, _, _)
+            | Pattern::Path(_)
+            | Pattern::TupleStruct(_, _)
+            | Pattern::Struct(_, _, _)
+            | Pattern::Or(_) => false,
+        }
+    }
+    /// The names this pattern binds.
+    pub fn bindings(&self) -> Vec<&str> {
+        match self {
+            Pattern::Binding(name) | Pattern::Rest(Some(name)) => vec![name.as_str()],
+            Pattern::TupleStruct(_, elements)
+            | Pattern::Tuple(elements)
+            | Pattern::Array(elements) => elements.iter().flat_map(Pattern::bindings).collect(),
+            Pattern::Struct(_, fields, _) | Pattern::Map(fields, _) => {
+                fields.iter().flat_map(|(_, p)| p.bindings()).collect()
+            }
+            // Parsing made sure every alternative binds the same names
+            Pattern::Or(alternatives) => alternatives
+                .first()
+                .map_or_else(Vec::new, Pattern::bindings),
+            Pattern::Wildcard
+            | Pattern::Rest(None)
+            | Pattern::Literal(_)
+            | Pattern::Range(_, _, _)
+            | Pattern::Path(_) => Vec::new(),
         }
     }
 }
@@ -0,0 +1,215 @@
+use crate::parser::{Expression, Pattern, Statement, Type};
+
+/// The names a lambda's body uses without binding them itself, in order of first use.
+///
+/// Names of globals are included too, as the parser can't tell them apart from
+/// locals of the enclosing function. Nested lambdas aren't walked again, as their
+/// captures are already known.
+pub fn free_variables(params: &[(Pattern, Option<Type>)], body: &Expression) -> Vec<String> {
+    let mut cx = Captures {
+        bound: params
+            .iter()
+            .flat_map(|(pattern, _)| pattern.bindings())
+            .map(str::to_string)
+            .collect(),
+        free: Vec::new(),
+    };
+    cx.expression(body);
+    cx.free
+}
+
+struct Captures {
+    /// Names bound at the current position, innermost last.
+    bound: Vec<String>,
+    free: Vec<String>,
+}
+impl Captures {
+    fn use_name(&mut self, name: &str) {
+        if !self.bound.iter().any(|b| b == name) && !self.free.iter().any(|f| f == name) {
+            self.free.push(name.to_string());
+        }
+    }
+    /// Runs `f` with `names` bound, unbinding them afterwards.
+    fn scoped<'a>(&mut self, names: impl IntoIterator<Item = &'a str>, f: impl FnOnce(&mut Self)) {
+        let len = self.bound.len();
+        self.bound.extend(names.into_iter().map(str::to_string));
+        f(self);
+        self.bound.truncate(len);
+    }
+    // Type annotations aren't walked, since they name types rather than values
+    fn statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Annotated(_, statement) | Statement::Modified(_, statement) => {
+                self.statement(statement)
+            }
+            Statement::ConstructorDecl(params, body) => {
+                self.scoped(params.iter().flat_map(|(p, _)| p.bindings()), |cx| {
+                    cx.expression(body)
+                });
+            }
+            Statement::FunctionDecl {
+                name, params, body, ..
+            } => {
+                self.bound.push(name.clone());
+                if let Some(body) = body {
+                    self.scoped(params.iter().flat_map(|(p, _)| p.bindings()), |cx| {
+                        cx.expression(body)
+                    });
+                }
+            }
+            Statement::ClassDecl { name, members, .. }
+            | Statement::InterfaceDecl { name, members, .. } => {
+                self.bound.push(name.clone());
+                self.scoped([], |cx| {
+                    for member in members {
+                        cx.statement(member);
+                    }
+                });
+            }
+            Statement::EnumDecl { name, .. } => self.bound.push(name.clone()),
+            Statement::Import(imports) => {
+                for import in imports {
+                    self.bound
+                        .extend(import.names().into_iter().map(str::to_string));
+                }
+            }
+            Statement::Return(value) => {
+                if let Some(value) = value {
+                    self.expression(value);
+                }
+            }
+            Statement::VariableDecl { pattern, value, .. } => {
+                if let Some(value) = value {
+                    self.expression(value);
+                }
+                self.bound
+                    .extend(pattern.bindings().into_iter().map(str::to_string));
+            }
+            Statement::Assign(left, _, right, _) => {
+                self.expression(left);
+                self.expression(right);
+            }
+            Statement::Expression(expr) => self.expression(expr),
+        }
+    }
+    fn expression(&mut self, expr: &Expression) {
+        match expr {
+            Expression::Binary(left, _, right)
+            | Expression::Coalesce(left, right)
+            | Expression::Range {
+                start: left,
+                end: right,
+                ..
+            } => {
+                self.expression(left);
+                self.expression(right);
+            }
+            Expression::Conditional(cond, then, otherwise) => {
+                self.expression(cond);
+                self.expression(then);
+                self.expression(otherwise);
+            }
+            Expression::Unary(_, expr) => self.expression(expr),
+            Expression::Block(statements, value) => self.scoped([], |cx| {
+                for statement in statements {
+                    cx.statement(statement);
+                }
+                if let Some(value) = value {
+                    cx.expression(value);
+                }
+            }),
+            Expression::If(cond, then, otherwise) => {
+                self.expression(cond);
+                self.expression(then);
+                if let Some(otherwise) = otherwise {
+                    self.expression(otherwise);
+                }
+            }
+            Expression::While(_, cond, body) => {
+                self.expression(cond);
+                self.expression(body);
+            }
+            Expression::Loop(_, body) => self.expression(body),
+            Expression::For(_, item, iterable, body) => {
+                self.expression(iterable);
+                self.scoped(item.bindings(), |cx| cx.expression(body));
+            }
+            Expression::Match(value, arms) => {
+                self.expression(value);
+                for arm in arms {
+                    self.scoped(arm.pattern.bindings(), |cx| {
+                        if let Some(guard) = &arm.guard {
+                            cx.expression(guard);
+                        }
+                        cx.expression(&arm.body);
+                    });
+                }
+            }
+            Expression::Break(_, value) => {
+                if let Some(value) = value {
+                    self.expression(value);
+                }
+            }
+            Expression::Continue(_) => {}
+            Expression::Try {
+                body,
+                catch,
+                finally,
+            } => {
+                self.expression(body);
+                if let Some(catch) = catch {
+                    let bindings = catch.pattern.iter().flat_map(Pattern::bindings);
+                    self.scoped(bindings, |cx| cx.expression(&catch.body));
+                }
+                if let Some(finally) = finally {
+                    self.expression(finally);
+                }
+            }
+            Expression::Throw(error) => self.expression(error),
+            // The inner lambda already worked out what it needs from outside
+            Expression::Lambda { captures, .. } => {
+                for name in captures {
+                    self.use_name(name);
+                }
+            }
+            Expression::Array(items) => {
+                for item in items {
+                    self.expression(item);
+                }
+            }
+            Expression::Map(entries) => {
+                for (_, value) in entries {
+                    self.expression(value);
+                }
+            }
+            Expression::Spread(expr) => self.expression(expr),
+            Expression::Call { callee, args, .. } => {
+                self.expression(callee);
+                for arg in args {
+                    self.expression(arg);
+                }
+            }
+            Expression::Member { object, .. } => self.expression(object),
+            Expression::Index { object, index } => {
+                self.expression(object);
+                self.expression(index);
+            }
+            Expression::Identifier(name) => self.use_name(name),
+            // `self` is a keyword, so it can't clash with a captured variable, and `super`
+            // calls methods on `self` too
+            Expression::Super | Expression::SelfValue => self.use_name("self"),
+            Expression::New { args, .. } => {
+                for arg in args {
+                    self.expression(arg);
+                }
+            }
+            Expression::StringLiteral(_)
+            | Expression::SignedLiteral(_)
+            | Expression::UnsignedLiteral(_)
+            | Expression::FloatLiteral(_)
+            | Expression::BoolLiteral(_)
+            | Expression::CharLiteral(_)
+            | Expression::NullLiteral => {}
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use crate::error::{closest_match, Applicability, Diagnostic};
+use crate::parser::{Import, Statement, Visibility};
+use std::ops::Range;
+
+/// A parsed source file, named by its path like `["ui", "button"]` for `ui/button.owl`.
+#[derive(Debug)]
+pub struct Module {
+    pub path: Vec<String>,
+    pub statements: Vec<Statement>,
+}
+impl Module {
+    /// The top-level declaration named `name`, if there is one.
+    fn item(&self, name: &str) -> Option<&Statement> {
+        self.statements.iter().find(|s| s.name() == Some(name))
+    }
+}
+
+/// Checks that the imports of `module` only reach items that other modules export,
+/// and only public members of their classes.
+///
+/// Absolute imports that don't start with the path of one of `modules` are left
+/// alone, as they may refer to the environment, like `Console`. Relative imports
+/// must be found among `modules`, and JavaScript modules aren't checked at all.
+pub fn check_imports(module: &Module, modules: &[Module]) -> Vec<Diagnostic> {
+    let package = &module.path[..module.path.len().saturating_sub(1)];
+    let mut diagnostics = Vec::new();
+    for statement in &module.statements {
+        let Statement::Import(imports) = statement.inner() else {
+            continue;
+        };
+        for import in imports {
+            let Import::Tree { relative, tree } = import else {
+                continue;
+            };
+            for (path, leaf) in tree.leaves() {
+                let path = match relative {
+                    true => [package, &path].concat(),
+                    false => path,
+                };
+                if let Err(diagnostic) = resolve(module, modules, &path, *relative, leaf.span()) {
+                    diagnostics.push(diagnostic);
+                }
+            }
+        }
+    }
+    diagnostics
+}
+
+fn resolve(
+    module: &Module,
+    modules: &[Module],
+    path: &[String],
+    relative: bool,
+    span: &Range<usize>,
+) -> Result<(), Diagnostic> {
+    // The module with the longest path that the import starts with
+    let Some(target) = modules
+        .iter()
+        .filter(|m| path.starts_with(&m.path))
+        .max_by_key(|m| m.path.len())
+    else {
+        // A package, like `ui` for `ui.widget`, can be imported with a glob
+        if !relative || modules.iter().any(|m| m.path.starts_with(path)) {
+            return Ok(());
+        }
+        let package_len = module.path.len().saturating_sub(1);
+        error!(
+            span.clone(),
+            "`{}` not found in the current package",
+            path[package_len..].join(".")
+        );
+    };
+    // Everything in a file is visible inside of it, and a whole file may be imported
+    if target.path == module.path || target.path.len() == path.len() {
+        return Ok(());
+    }
+    let name = &path[target.path.len()];
+    let Some(item) = target.item(name) else {
+        let mut diagnostic = Diagnostic::error(
+            span.clone(),
+            format!("`{}` not found in `{}`", name, target.path.join(".")),
+        );
+        let names = target.statements.iter().filter_map(Statement::name);
+        if let Some(candidate) = closest_match(name, names) {
+            let mut replacement = path.to_vec();
+            replacement[target.path.len()] = candidate.to_string();
+            diagnostic = diagnostic.with_suggestion(
+                format!("did you mean `{}`?", candidate),
+                span.clone(),
+                replacement.join("."),
+                Applicability::MaybeIncorrect,
+            );
+        }
+        return Err(diagnostic);
+    };
+    if item.visibility() != Some(Visibility::Public) {
+        error!(
+            span.clone(),
+            "`{}` is private to `{}`",
+            name,
+            target.path.join(".")
+        );
+    }
+    let Some(member_name) = path.get(target.path.len() + 1) else {
+        return Ok(());
+    };
+    let members = match item.inner() {
+        Statement::ClassDecl { members, .. } | Statement::InterfaceDecl { members, .. } => members,
+        // Variants are as visible as their enum
+        Statement::EnumDecl { variants, .. } if variants.iter().any(|v| v.name == *member_name) => {
+            return Ok(());
+        }
+        Statement::EnumDecl { .. } => {
+            error!(span.clone(), "`{}` has no member `{}`", name, member_name)
+        }
+        _ => return Ok(()),
+    };
+    match members.iter().find(|m| m.name() == Some(member_name)) {
+        Some(member) if member.visibility() == Some(Visibility::Private) => {
+            error!(
+                span.clone(),
+                "`{}` is a private member of `{}`", member_name, name
+            )
+        }
+        Some(_) => Ok(()),
+        None => error!(span.clone(), "`{}` has no member `{}`", name, member_name),
+    }
+}